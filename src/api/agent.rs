@@ -1,6 +1,14 @@
+#[cfg(unix)]
+use crate::api::unix::Socket;
+use crate::api::{
+    cache::{Cache, Stale},
+    response::Response,
+    timings::{Timed, Timing, Timings},
+    trace::{Recorder, Replayer},
+};
 use anyhow::Context;
 use secrecy::{ExposeSecret, Secret};
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc};
 use url::Url;
 
 #[derive(Debug, serde::Deserialize)]
@@ -12,6 +20,98 @@ struct ErrorResponse {
 pub(super) struct Agent {
     base: Url,
     agent: Secret<UreqAgent>,
+    /// Used instead of `agent` when connecting over a unix domain socket
+    #[cfg(unix)]
+    socket: Option<Socket>,
+    /// Only set when it's sent as a header, otherwise it's in the cookie store
+    auth_token: Option<Secret<String>>,
+    options: Options,
+    stale: Stale,
+}
+
+/// How to authenticate requests to the proxy
+#[derive(Debug, Clone, Default)]
+crate struct Auth {
+    crate mechanism: AuthMechanism,
+    /// Sent with every request, e.g. for a gateway in front of the proxy
+    crate headers: Vec<(String, Secret<String>)>,
+}
+
+/// How the auth token is sent to the proxy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+crate enum AuthMechanism {
+    /// An `auth-token` cookie, as the proxy itself expects
+    Cookie,
+    /// An `Authorization: Bearer` header
+    Bearer,
+}
+
+/// How the agent talks to the proxy, independent of how it authenticates
+#[derive(Debug, Clone, Default)]
+pub(super) struct Options {
+    pub(super) cache: Option<Cache>,
+    /// Never contact the proxy, only use the cache
+    pub(super) offline: bool,
+    /// Why the proxy couldn't be reached, when it's offline without that being requested
+    pub(super) unreachable: Option<String>,
+    pub(super) recorder: Option<Arc<Recorder>>,
+    pub(super) replayer: Option<Arc<Replayer>>,
+    pub(super) auth: Auth,
+    pub(super) timings: Option<Timings>,
+    #[cfg(unix)]
+    pub(super) socket: Option<std::path::PathBuf>,
+    #[cfg(feature = "tls")]
+    pub(super) tls: crate::api::Tls,
+}
+
+impl Default for AuthMechanism {
+    fn default() -> Self {
+        Self::Cookie
+    }
+}
+
+impl std::str::FromStr for AuthMechanism {
+    type Err = anyhow::Error;
+
+    #[fehler::throws(Self::Err)]
+    fn from_str(s: &str) -> Self {
+        match s {
+            "cookie" => Self::Cookie,
+            "bearer" => Self::Bearer,
+            _ => anyhow::bail!("unknown auth mechanism {:?}, expected cookie or bearer", s),
+        }
+    }
+}
+
+impl std::fmt::Display for AuthMechanism {
+    #[fehler::throws(std::fmt::Error)]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) {
+        match self {
+            Self::Cookie => write!(f, "cookie")?,
+            Self::Bearer => write!(f, "bearer")?,
+        }
+    }
+}
+
+impl Options {
+    #[fehler::throws(anyhow::Error)]
+    fn ureq_builder(&self, base: &Url) -> ureq::AgentBuilder {
+        anyhow::ensure!(
+            !base.cannot_be_a_base(),
+            "Invalid base url, must be able to append components"
+        );
+        let builder = ureq::builder();
+        #[cfg(feature = "tls")]
+        let builder = builder.tls_config(self.tls.config()?);
+        #[cfg(not(feature = "tls"))]
+        anyhow::ensure!(
+            base.scheme() != "https",
+            "https base urls need {} to be built with the `tls` feature",
+            env!("CARGO_PKG_NAME"),
+        );
+        builder
+    }
 }
 
 struct UreqAgent(ureq::Agent);
@@ -35,37 +135,114 @@ impl std::fmt::Debug for Agent {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) {
         f.debug_struct("Agent")
             .field("base", &self.base.to_string())
+            .field("options", &self.options)
             .finish()?;
     }
 }
 
 impl Agent {
+    #[fehler::throws(anyhow::Error)]
+    fn new(base: Url, cookies: cookie_store::CookieStore, options: Options) -> Self {
+        let builder = options.ureq_builder(&base)?;
+        #[cfg(unix)]
+        if let Some(path) = &options.socket {
+            return Self {
+                base,
+                agent: Secret::new(UreqAgent(builder.build())),
+                socket: Some(Socket::new(path.clone(), cookies)),
+                auth_token: None,
+                options,
+                stale: Stale::default(),
+            };
+        }
+        Self {
+            base,
+            agent: Secret::new(UreqAgent(builder.cookie_store(cookies).build())),
+            #[cfg(unix)]
+            socket: None,
+            auth_token: None,
+            options,
+            stale: Stale::default(),
+        }
+    }
+
+    /// The value of a cookie set by the proxy
+    fn cookie(&self, domain: &str, name: &str) -> Option<Secret<String>> {
+        #[cfg(unix)]
+        if let Some(socket) = &self.socket {
+            return socket.cookie(domain, name);
+        }
+        let cookies = self.agent.expose_secret().cookie_store();
+        let cookie = cookies.get(domain, "/", name)?;
+        Some(Secret::new(cookie.value().to_owned()))
+    }
+
+    #[fehler::throws(anyhow::Error)]
+    #[tracing::instrument(fields(%base))]
+    /// An agent that doesn't log in, for when the responses come from the cache or a trace
+    pub(super) fn without_login(base: Url, options: Options) -> Self {
+        Self::new(base, empty_cookies(), options)?
+    }
+
+    pub(super) fn is_offline(&self) -> bool {
+        self.options.offline
+    }
+
+    pub(super) fn is_replaying(&self) -> bool {
+        self.options.replayer.is_some()
+    }
+
+    pub(super) fn unreachable(&self) -> Option<&str> {
+        self.options.unreachable.as_deref()
+    }
+
+    pub(super) fn stale(&self) -> Stale {
+        self.stale.clone()
+    }
+
+    pub(super) fn timings(&self) -> Option<Timings> {
+        self.options.timings.clone()
+    }
+
     #[fehler::throws(anyhow::Error)]
     #[tracing::instrument(fields(%base))]
-    pub(super) fn with_token(base: Url, auth_token: Secret<String>) -> Option<Self> {
+    pub(super) fn with_token(
+        base: Url,
+        auth_token: Secret<String>,
+        options: Options,
+    ) -> Option<Self> {
         anyhow::ensure!(
             !base.cannot_be_a_base(),
             "Invalid base url, must be able to append components"
         );
-        let domain = base
-            .domain()
-            .context("Invalid base url, must contain a domain to attach cookie to")?
-            .to_owned();
-        let mut cookies = cookie_store::CookieStore::load_json(std::io::Cursor::new("")).unwrap();
-        cookies.insert_raw(
-            &cookie::Cookie::build("auth-token", auth_token.expose_secret().to_owned())
-                .domain(domain)
-                .path("/")
-                .finish(),
-            &base,
-        )?;
-        let agent = Self {
-            base,
-            agent: Secret::new(UreqAgent(ureq::builder().cookie_store(cookies).build())),
+        let agent = match options.auth.mechanism {
+            AuthMechanism::Cookie => {
+                let domain = base
+                    .domain()
+                    .context("Invalid base url, must contain a domain to attach cookie to")?
+                    .to_owned();
+                let mut cookies = empty_cookies();
+                cookies.insert_raw(
+                    &cookie::Cookie::build("auth-token", auth_token.expose_secret().to_owned())
+                        .domain(domain)
+                        .path("/")
+                        .finish(),
+                    &base,
+                )?;
+                Self::new(base, cookies, options)?
+            }
+            AuthMechanism::Bearer => Self {
+                auth_token: Some(auth_token),
+                ..Self::new(base, empty_cookies(), options)?
+            },
         };
-        match agent.get(["v1", "identities"]) {
-            Ok(t) => {
-                let _: ureq::SerdeValue = t;
+        let url = ["v1", "identities"].append_to(agent.base.clone());
+        match agent.fetch(&url) {
+            Ok(identities) => {
+                // Saves fetching them again if the command wants them
+                if let Some(cache) = &agent.options.cache {
+                    cache.store(&agent.base, &url, &identities);
+                }
                 Some(agent)
             }
             Err(crate::api::Error::Api { code, .. }) if code == 403 => None,
@@ -75,7 +252,11 @@ impl Agent {
 
     #[fehler::throws(anyhow::Error)]
     #[tracing::instrument(fields(%base))]
-    pub(super) fn with_login(base: Url, passphrase: Secret<String>) -> (Self, Secret<String>) {
+    pub(super) fn with_login(
+        base: Url,
+        passphrase: Secret<String>,
+        options: Options,
+    ) -> (Self, Secret<String>) {
         #[derive(Debug, serde::Serialize)]
         #[serde(rename_all = "camelCase")]
         struct LoginData {
@@ -104,24 +285,23 @@ impl Agent {
             .context("Invalid base url, must contain a domain to attach cookie to")?
             .to_owned();
 
-        let agent = Self {
-            base,
-            agent: Secret::new(UreqAgent(ureq::agent())),
-        };
+        let mut agent = Self::new(base, empty_cookies(), options)?;
 
-        let crate::api::Nothing =
-            agent.post(["v1", "keystore", "unseal"], LoginData { passphrase })?;
-
-        let auth_token = Secret::new(
-            agent
-                .agent
-                .expose_secret()
-                .cookie_store()
-                .get(&domain, "/", "auth-token")
-                .context("Missing auth token after login")?
-                .value()
-                .to_owned(),
-        );
+        // Not `post`, unsealing doesn't change any data so mustn't clear the cache
+        let url = ["v1", "keystore", "unseal"].append_to(agent.base.clone());
+        let data = ureq::serde_to_value(LoginData { passphrase })?;
+        let crate::api::Nothing = agent
+            .call("POST", &url, Some(data))?
+            .check_error()?
+            .into_json()?;
+
+        let auth_token = agent
+            .cookie(&domain, "auth-token")
+            .context("Missing auth token after login")?;
+
+        if agent.options.auth.mechanism == AuthMechanism::Bearer {
+            agent.auth_token = Some(auth_token.clone());
+        }
 
         // The web server resets itself after login...
         std::thread::sleep(std::time::Duration::from_millis(100));
@@ -136,9 +316,7 @@ impl Agent {
         path: impl UrlComponents + Debug,
     ) -> T {
         let url = path.append_to(self.base.clone());
-        let response = self.agent.expose_secret().get(&url.to_string()).call();
-        tracing::debug!(%url, ?response);
-        let value = response.check_error()?.into_json()?;
+        let value = serde_json::from_value(self.cached(&url)?)?;
         tracing::trace!(?value);
         value
     }
@@ -150,35 +328,276 @@ impl Agent {
         path: impl UrlComponents + Debug,
     ) -> Option<T> {
         let url = path.append_to(self.base.clone());
-        let response = self.agent.expose_secret().get(&url.to_string()).call();
-        tracing::debug!(%url, ?response);
-        let value = if let Err(ureq::Error::Status(404, _)) = response {
-            None
-        } else {
-            Some(response.check_error()?.into_json()?)
+        let value = match self.cached(&url) {
+            Err(crate::api::Error::Api { code: 404, .. }) => None,
+            value => Some(serde_json::from_value(value?)?),
         };
         tracing::trace!(?value);
         value
     }
 
+    #[allow(dead_code)] // Not needed by any wrapped endpoint yet
+    #[fehler::throws(crate::api::Error)]
+    #[tracing::instrument]
+    /// Get a response body without buffering or decoding it, for large blobs (never cached)
+    pub(super) fn get_reader(
+        &self,
+        path: impl UrlComponents + Debug,
+    ) -> impl std::io::Read + Send {
+        if self.options.offline {
+            fehler::throw!(self.offline_error(crate::api::Error::Offline));
+        }
+        let url = path.append_to(self.base.clone());
+        self.call("GET", &url, None)?.check_error()?.into_reader()
+    }
+
     #[fehler::throws(crate::api::Error)]
     #[tracing::instrument]
     pub(super) fn post<T: serde::de::DeserializeOwned + Debug>(
         &self,
         path: impl UrlComponents + Debug,
         data: impl serde::Serialize + Debug,
+    ) -> T {
+        self.send("POST", path, Some(ureq::serde_to_value(data)?))?
+    }
+
+    #[allow(dead_code)] // Not needed by any wrapped endpoint yet
+    #[fehler::throws(crate::api::Error)]
+    #[tracing::instrument]
+    pub(super) fn put<T: serde::de::DeserializeOwned + Debug>(
+        &self,
+        path: impl UrlComponents + Debug,
+        data: impl serde::Serialize + Debug,
+    ) -> T {
+        self.send("PUT", path, Some(ureq::serde_to_value(data)?))?
+    }
+
+    #[allow(dead_code)] // Not needed by any wrapped endpoint yet
+    #[fehler::throws(crate::api::Error)]
+    #[tracing::instrument]
+    pub(super) fn patch<T: serde::de::DeserializeOwned + Debug>(
+        &self,
+        path: impl UrlComponents + Debug,
+        data: impl serde::Serialize + Debug,
+    ) -> T {
+        self.send("PATCH", path, Some(ureq::serde_to_value(data)?))?
+    }
+
+    #[allow(dead_code)] // Not needed by any wrapped endpoint yet
+    #[fehler::throws(crate::api::Error)]
+    #[tracing::instrument]
+    pub(super) fn delete<T: serde::de::DeserializeOwned + Debug>(
+        &self,
+        path: impl UrlComponents + Debug,
+    ) -> T {
+        self.send("DELETE", path, None)?
+    }
+
+    #[fehler::throws(crate::api::Error)]
+    // The data may contain secrets, e.g. the passphrase when unsealing
+    #[tracing::instrument(skip(data))]
+    /// Send an arbitrary request to `path` and its query exactly as given, bypassing the cache,
+    /// returning the response body
+    pub(super) fn raw(&self, method: &str, path: &str, data: Option<ureq::SerdeValue>) -> String {
+        let url = Url::parse(&format!(
+            "{}/{}",
+            self.base.as_str().trim_end_matches('/'),
+            path.trim_start_matches('/')
+        ))?;
+        let response = if method == "GET" {
+            if self.options.offline {
+                fehler::throw!(self.offline_error(crate::api::Error::NotCached(url)));
+            }
+            self.call(method, &url, data)?.check_error()?
+        } else {
+            self.modify(method, &url, data)?
+        };
+        let body = response.into_string()?;
+        tracing::trace!(%body);
+        body
+    }
+
+    #[fehler::throws(crate::api::Error)]
+    fn send<T: serde::de::DeserializeOwned + Debug>(
+        &self,
+        method: &str,
+        path: impl UrlComponents,
+        data: Option<ureq::SerdeValue>,
     ) -> T {
         let url = path.append_to(self.base.clone());
-        let response = self
-            .agent
-            .expose_secret()
-            .post(&url.to_string())
-            .send_json(ureq::serde_to_value(data)?);
-        tracing::debug!(%url, ?response);
-        let value = response.check_error()?.into_json()?;
+        let value = self.modify(method, &url, data)?.into_json()?;
         tracing::trace!(?value);
         value
     }
+
+    /// Send a request that may modify data on the proxy, so invalidates the cache
+    #[fehler::throws(crate::api::Error)]
+    fn modify(&self, method: &str, url: &Url, data: Option<ureq::SerdeValue>) -> Response {
+        if self.options.offline {
+            fehler::throw!(self.offline_error(crate::api::Error::Offline));
+        }
+        let response = self.call(method, url, data)?;
+        if let Some(cache) = &self.options.cache {
+            cache.clear(&self.base);
+        }
+        response.check_error()?
+    }
+
+    /// Get `url`, using the cache if the entry is fresh or the proxy is unavailable
+    #[fehler::throws(crate::api::Error)]
+    fn cached(&self, url: &Url) -> ureq::SerdeValue {
+        let offline = self.options.offline;
+        let cache = match &self.options.cache {
+            Some(cache) => cache,
+            None if offline => {
+                fehler::throw!(self.offline_error(crate::api::Error::NotCached(url.clone())))
+            }
+            None => return self.fetch(url)?,
+        };
+
+        let hit = cache.load(&self.base, url);
+        match hit {
+            Some(hit) if hit.age < cache.ttl() && !offline => return hit.value,
+            Some(hit) if offline => {
+                // Entries of any age are fine when being offline was requested, but still worth
+                // pointing out if they're older than they would otherwise be used
+                if self.options.unreachable.is_some() || hit.age >= cache.ttl() {
+                    self.stale.push(url.clone(), hit.age);
+                }
+                return hit.value;
+            }
+            None if offline => {
+                fehler::throw!(self.offline_error(crate::api::Error::NotCached(url.clone())))
+            }
+            _ => {}
+        }
+
+        match self.fetch(url) {
+            Ok(value) => {
+                cache.store(&self.base, url, &value);
+                value
+            }
+            Err(crate::api::Error::UreqTransport(err)) => match hit {
+                Some(hit) => {
+                    tracing::debug!(%err, "proxy unreachable, using stale cache entry");
+                    self.stale.push(url.clone(), hit.age);
+                    hit.value
+                }
+                None => fehler::throw!(crate::api::Error::UreqTransport(err)),
+            },
+            Err(err) => fehler::throw!(err),
+        }
+    }
+
+    /// Reports why the proxy couldn't be reached instead of `err` if it wasn't meant to be offline
+    fn offline_error(&self, err: crate::api::Error) -> crate::api::Error {
+        match &self.options.unreachable {
+            Some(reason) => crate::api::Error::Unreachable(reason.clone()),
+            None => err,
+        }
+    }
+
+    #[fehler::throws(crate::api::Error)]
+    fn fetch(&self, url: &Url) -> ureq::SerdeValue {
+        self.call("GET", url, None)?.check_error()?.into_json()?
+    }
+
+    /// Sends a request to the proxy, or serves it from the trace being replayed, and records how
+    /// long it took
+    #[fehler::throws(crate::api::Error)]
+    fn call(&self, method: &str, url: &Url, data: Option<ureq::SerdeValue>) -> Response {
+        let endpoint = url
+            .as_str()
+            .strip_prefix(self.base.as_str())
+            .unwrap_or_else(|| url.as_str());
+
+        let start = std::time::Instant::now();
+        let response = self.exchange(method, url, endpoint, data);
+        let timings = match &self.options.timings {
+            Some(timings) => timings,
+            None => return response?,
+        };
+        let timing = Timing {
+            method: method.to_owned(),
+            endpoint: endpoint.to_owned(),
+            status: None,
+            size: None,
+            duration: start.elapsed(),
+        };
+        match response {
+            // Recorded once the body has been read, so the size and time are for all of it
+            Ok(response) => {
+                let timing = Timing {
+                    status: Some(response.status()),
+                    size: Some(0),
+                    ..timing
+                };
+                let timings = timings.clone();
+                response.map_body(move |body| Timed::new(body, timings, timing, start))
+            }
+            Err(err) => {
+                timings.push(timing);
+                fehler::throw!(err)
+            }
+        }
+    }
+
+    #[fehler::throws(crate::api::Error)]
+    fn exchange(
+        &self,
+        method: &str,
+        url: &Url,
+        endpoint: &str,
+        data: Option<ureq::SerdeValue>,
+    ) -> Response {
+        if let Some(replayer) = &self.options.replayer {
+            return replayer.replay(method, endpoint)?;
+        }
+
+        let mut headers = self.options.auth.headers.clone();
+        if let Some(auth_token) = &self.auth_token {
+            let header = Secret::new(format!("Bearer {}", auth_token.expose_secret()));
+            headers.push(("Authorization".to_owned(), header));
+        }
+
+        let response = self.send_request(method, url, &headers, data.as_ref())?;
+        tracing::debug!(%url, ?response);
+
+        match &self.options.recorder {
+            Some(recorder) => recorder.record(method, endpoint, data.as_ref(), response)?,
+            None => response,
+        }
+    }
+
+    #[fehler::throws(crate::api::Error)]
+    fn send_request(
+        &self,
+        method: &str,
+        url: &Url,
+        headers: &[(String, Secret<String>)],
+        data: Option<&ureq::SerdeValue>,
+    ) -> Response {
+        #[cfg(unix)]
+        if let Some(socket) = &self.socket {
+            return socket.send(method, url, headers, data)?;
+        }
+        let mut request = self.agent.expose_secret().request(method, url.as_str());
+        for (name, value) in headers {
+            request = request.set(name, value.expose_secret());
+        }
+        let response = match data {
+            Some(data) => request.send_json(data.clone()),
+            None => request.call(),
+        };
+        match response {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => Response::from_ureq(response),
+            Err(err) => fehler::throw!(Box::new(err)),
+        }
+    }
+}
+
+fn empty_cookies() -> cookie_store::CookieStore {
+    cookie_store::CookieStore::load_json(std::io::Cursor::new("")).unwrap()
 }
 
 pub(super) trait UrlComponents {
@@ -200,6 +619,13 @@ impl UrlComponents for String {
     }
 }
 
+impl<T: UrlComponents> UrlComponents for Vec<T> {
+    fn append_to(self, url: Url) -> Url {
+        self.into_iter()
+            .fold(url, |url, component| component.append_to(url))
+    }
+}
+
 impl<T> UrlComponents for &[T]
 where
     for<'a> &'a T: UrlComponents,
@@ -222,23 +648,203 @@ impl<T: UrlComponents, U: UrlComponents> UrlComponents for (T, U) {
     }
 }
 
+/// Query parameters to append to a url
+#[derive(Debug)]
+pub(super) struct Query(Vec<(String, String)>);
+
+impl Query {
+    /// From anything that serializes to a flat map, `None` fields are skipped
+    #[fehler::throws(serde_json::Error)]
+    pub(super) fn new(params: impl serde::Serialize) -> Self {
+        let params = match serde_json::to_value(params)? {
+            ureq::SerdeValue::Object(params) => params,
+            ureq::SerdeValue::Null => serde_json::Map::new(),
+            other => fehler::throw!(<serde_json::Error as serde::ser::Error>::custom(format!(
+                "query parameters must serialize to a map, got {}",
+                other
+            ))),
+        };
+        Self(
+            params
+                .into_iter()
+                .filter_map(|(key, value)| match value {
+                    ureq::SerdeValue::Null => None,
+                    ureq::SerdeValue::String(value) => Some((key, value)),
+                    value => Some((key, value.to_string())),
+                })
+                .collect(),
+        )
+    }
+}
+
+impl UrlComponents for Query {
+    fn append_to(self, mut url: Url) -> Url {
+        if !self.0.is_empty() {
+            url.query_pairs_mut().extend_pairs(self.0);
+        }
+        url
+    }
+}
+
 trait ApiResponseExt: Sized {
     #[fehler::throws(crate::api::Error)]
-    fn check_error(self) -> ureq::Response;
+    fn check_error(self) -> Response;
 }
 
-impl ApiResponseExt for Result<ureq::Response, ureq::Error> {
+impl ApiResponseExt for Response {
     #[fehler::throws(crate::api::Error)]
-    fn check_error(self) -> ureq::Response {
-        match self {
-            Ok(response) => response,
-            Err(ureq::Error::Status(code, response)) => {
-                let ErrorResponse { message: msg, .. } = response.into_json()?;
-                fehler::throw!(crate::api::Error::Api { msg, code });
-            }
-            Err(ureq::Error::Transport(transport)) => {
-                fehler::throw!(Box::new(ureq::Error::Transport(transport)))
+    fn check_error(self) -> Response {
+        let code = self.status();
+        if code < 400 {
+            return self;
+        }
+        let status_text = self.status_text().to_owned();
+        let body = String::from_utf8_lossy(&self.into_bytes()?).into_owned();
+        let (msg, variant) = match serde_json::from_str(&body) {
+            Ok(ErrorResponse { message, variant }) => (message, variant.into()),
+            Err(err) => {
+                tracing::debug!(%err, %body, "error response was not an ErrorResponse");
+                let msg = if body.trim().is_empty() {
+                    status_text
+                } else {
+                    body
+                };
+                (msg, crate::api::ErrorVariant::from_status(code))
             }
+        };
+        fehler::throw!(crate::api::Error::Api { msg, code, variant });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ApiResponseExt, Options, Query, Response, UrlComponents};
+    use crate::api::{Error, ErrorVariant};
+    use std::io::Cursor;
+    use url::Url;
+
+    fn with_query(url: &str, params: impl serde::Serialize) -> String {
+        let url: Url = url.parse().unwrap();
+        Query::new(params).unwrap().append_to(url).to_string()
+    }
+
+    #[test]
+    fn query_skips_none() {
+        #[derive(serde::Serialize)]
+        struct Params {
+            a: Option<&'static str>,
+            b: Option<&'static str>,
+        }
+        let params = Params {
+            a: None,
+            b: Some("x"),
+        };
+        assert_eq!(with_query("http://localhost/v1", params), "http://localhost/v1?b=x");
+    }
+
+    #[test]
+    fn query_stringifies_scalars() {
+        let params = serde_json::json!({ "flag": true, "count": 3, "name": "a b" });
+        assert_eq!(
+            with_query("http://localhost/v1", params),
+            "http://localhost/v1?count=3&flag=true&name=a+b"
+        );
+    }
+
+    #[test]
+    fn query_merges_with_existing() {
+        let params = serde_json::json!({ "b": "2" });
+        assert_eq!(
+            with_query("http://localhost/v1?a=1", params),
+            "http://localhost/v1?a=1&b=2"
+        );
+    }
+
+    #[test]
+    fn query_without_params() {
+        assert_eq!(with_query("http://localhost/v1", ()), "http://localhost/v1");
+    }
+
+    #[test]
+    fn query_must_be_a_map() {
+        assert!(Query::new(["a", "b"]).is_err());
+        assert!(Query::new("a").is_err());
+    }
+
+    fn check_error(code: u16, status_text: &str, body: &str) -> Result<Response, Error> {
+        let body = Cursor::new(body.to_owned());
+        Response::new(code, status_text.to_owned(), Vec::new(), body).check_error()
+    }
+
+    /// The message, code and variant of the error `check_error` makes of the response
+    fn api_error(code: u16, status_text: &str, body: &str) -> (String, u16, ErrorVariant) {
+        match check_error(code, status_text, body) {
+            Err(Error::Api { msg, code, variant }) => (msg, code, variant),
+            other => panic!("expected an api error, got {:?}", other),
         }
     }
+
+    #[test]
+    fn success_is_not_an_error() {
+        let response = check_error(200, "OK", "[]").unwrap();
+        assert_eq!(response.into_string().unwrap(), "[]");
+    }
+
+    #[test]
+    fn error_response_body() {
+        let body = r#"{ "message": "keystore is sealed", "variant": "KEYSTORE_SEALED" }"#;
+        assert_eq!(
+            api_error(403, "Forbidden", body),
+            ("keystore is sealed".to_owned(), 403, ErrorVariant::KeystoreSealed)
+        );
+    }
+
+    #[test]
+    fn plain_text_body() {
+        assert_eq!(
+            api_error(404, "Not Found", "no route for /v1/nope"),
+            ("no route for /v1/nope".to_owned(), 404, ErrorVariant::NotFound)
+        );
+        assert_eq!(
+            api_error(502, "Bad Gateway", "upstream timed out"),
+            (
+                "upstream timed out".to_owned(),
+                502,
+                ErrorVariant::Unknown("502".to_owned())
+            )
+        );
+    }
+
+    #[test]
+    fn empty_body_uses_status_text() {
+        assert_eq!(
+            api_error(401, "Unauthorized", ""),
+            ("Unauthorized".to_owned(), 401, ErrorVariant::Unauthorized)
+        );
+        assert_eq!(
+            api_error(500, "Internal Server Error", " \n"),
+            ("Internal Server Error".to_owned(), 500, ErrorVariant::Internal)
+        );
+    }
+
+    #[test]
+    fn unknown_variant_is_kept() {
+        let body = r#"{ "message": "try again later", "variant": "RATE_LIMITED" }"#;
+        assert_eq!(
+            api_error(400, "Bad Request", body),
+            (
+                "try again later".to_owned(),
+                400,
+                ErrorVariant::Unknown("RATE_LIMITED".to_owned())
+            )
+        );
+    }
+
+    #[cfg(not(feature = "tls"))]
+    #[test]
+    fn https_needs_tls_feature() {
+        let base = "https://localhost:17246".parse().unwrap();
+        let err = Options::default().ureq_builder(&base).err().unwrap();
+        assert!(err.to_string().contains("`tls` feature"), "{}", err);
+    }
 }
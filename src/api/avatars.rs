@@ -0,0 +1,25 @@
+use crate::api::{agent::Query, identities::AvatarFallback, Error};
+
+crate struct Api<'a> {
+    agent: &'a crate::api::Agent,
+}
+
+impl<'a> Api<'a> {
+    pub(super) fn new(agent: &'a crate::api::Agent) -> Self {
+        Self { agent }
+    }
+
+    /// The generated avatar for any id, identities already come with theirs as `avatar_fallback`
+    #[fehler::throws]
+    #[tracing::instrument(skip(self))]
+    crate fn get(&self, id: &str) -> AvatarFallback {
+        #[derive(Debug, serde::Serialize)]
+        struct Params {
+            /// The proxy picks the emoji from a different set for identities
+            usage: &'static str,
+        }
+
+        self.agent
+            .get((["v1", "avatars", id], Query::new(Params { usage: "any" })?))?
+    }
+}
@@ -0,0 +1,122 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use url::Url;
+
+/// An on-disk cache of successful `GET` responses, keyed by base url and endpoint
+#[derive(Debug, Clone)]
+crate struct Cache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+struct Entry {
+    /// When the response was fetched, as seconds since the unix epoch
+    fetched: u64,
+    value: ureq::SerdeValue,
+}
+
+/// Records the responses that were served from the cache because the proxy was not available
+#[derive(Debug, Clone, Default)]
+crate struct Stale(Arc<Mutex<Vec<(Url, Duration)>>>);
+
+#[derive(Debug)]
+pub(super) struct Hit {
+    pub(super) value: ureq::SerdeValue,
+    pub(super) age: Duration,
+}
+
+impl Cache {
+    /// Entries younger than `ttl` are used instead of contacting the proxy, older entries are only
+    /// used when the proxy is unreachable
+    crate fn new(dir: PathBuf, ttl: Duration) -> Self {
+        Self { dir, ttl }
+    }
+
+    crate fn default_dir() -> Option<PathBuf> {
+        Some(dirs_next::cache_dir()?.join(env!("CARGO_PKG_NAME")))
+    }
+
+    pub(super) fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    fn base_dir(&self, base: &Url) -> PathBuf {
+        self.dir.join(escape(base.as_str()))
+    }
+
+    fn path(&self, base: &Url, url: &Url) -> PathBuf {
+        let endpoint = url.as_str().strip_prefix(base.as_str()).unwrap_or(url.as_str());
+        self.base_dir(base).join(escape(endpoint) + ".json")
+    }
+
+    #[tracing::instrument(skip(self), fields(%base, %url))]
+    pub(super) fn load(&self, base: &Url, url: &Url) -> Option<Hit> {
+        let path = self.path(base, url);
+        let entry: Entry = match std::fs::read(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|data| Ok(serde_json::from_slice(&data)?))
+        {
+            Ok(entry) => entry,
+            Err(err) => {
+                tracing::debug!(path = %path.display(), %err, "cache miss");
+                return None;
+            }
+        };
+        let fetched = SystemTime::UNIX_EPOCH + Duration::from_secs(entry.fetched);
+        let age = SystemTime::now()
+            .duration_since(fetched)
+            .unwrap_or_default();
+        Some(Hit {
+            value: entry.value,
+            age,
+        })
+    }
+
+    #[tracing::instrument(skip(self, value), fields(%base, %url))]
+    pub(super) fn store(&self, base: &Url, url: &Url, value: &ureq::SerdeValue) {
+        let path = self.path(base, url);
+        let fetched = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let result = std::fs::create_dir_all(self.base_dir(base))
+            .map_err(anyhow::Error::from)
+            .and_then(|()| Ok(serde_json::to_vec(&Entry { fetched, value: value.clone() })?))
+            .and_then(|data| Ok(std::fs::write(&path, data)?));
+        if let Err(err) = result {
+            tracing::debug!(path = %path.display(), %err, "failed to store cache entry");
+        }
+    }
+
+    /// Drops everything cached for `base`, any write could have changed any response
+    #[tracing::instrument(skip(self), fields(%base))]
+    pub(super) fn clear(&self, base: &Url) {
+        let dir = self.base_dir(base);
+        if let Err(err) = std::fs::remove_dir_all(&dir) {
+            if err.kind() != std::io::ErrorKind::NotFound {
+                tracing::debug!(dir = %dir.display(), %err, "failed to clear cache");
+            }
+        }
+    }
+}
+
+impl Stale {
+    pub(super) fn push(&self, url: Url, age: Duration) {
+        self.0.lock().unwrap().push((url, age));
+    }
+
+    /// The number of stale responses served, and the age of the oldest of them
+    crate fn summary(&self) -> Option<(usize, Duration)> {
+        let stale = self.0.lock().unwrap();
+        Some((stale.len(), stale.iter().map(|&(_, age)| age).max()?))
+    }
+}
+
+/// Makes an arbitrary string safe to use as a single path component
+fn escape(s: &str) -> String {
+    url::form_urlencoded::byte_serialize(s.as_bytes()).collect()
+}
@@ -0,0 +1,37 @@
+use crate::api::Api;
+use std::sync::Mutex;
+
+impl Api {
+    /// Calls `f` for each item with at most `jobs` calls running at once, returning the results in
+    /// the same order as the items
+    #[tracing::instrument(skip(self, items, f), fields(jobs = self.jobs.get()))]
+    crate fn concurrently<T, U>(&self, items: Vec<T>, f: impl Fn(&Api, T) -> U + Sync) -> Vec<U>
+    where
+        T: Send,
+        U: Send,
+    {
+        let count = items.len();
+        let queue = Mutex::new(items.into_iter().enumerate());
+        let results = Mutex::new(Vec::with_capacity(count));
+
+        crossbeam_utils::thread::scope(|scope| {
+            for _ in 0..self.jobs.get().min(count) {
+                scope.spawn(|_| loop {
+                    let next = queue.lock().unwrap().next();
+                    match next {
+                        Some((index, item)) => {
+                            let result = f(self, item);
+                            results.lock().unwrap().push((index, result));
+                        }
+                        None => break,
+                    }
+                });
+            }
+        })
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+
+        let mut results = results.into_inner().unwrap();
+        results.sort_by_key(|&(index, _)| index);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+}
@@ -23,7 +23,7 @@ crate struct AvatarFallback {
     crate background: Color,
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 crate struct Color {
     crate r: u8,
@@ -1,17 +1,44 @@
 use secrecy::Secret;
+use std::{
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+};
 use url::Url;
 
 mod agent;
+mod cache;
+mod concurrent;
+mod response;
+#[cfg(feature = "tls")]
+mod tls;
+mod timings;
+mod trace;
+#[cfg(unix)]
+mod unix;
+crate mod version;
+crate mod avatars;
 crate mod identities;
 crate mod projects;
+crate mod raw;
 crate mod session;
 
 use agent::Agent;
+crate use agent::{Auth, AuthMechanism};
+crate use cache::{Cache, Stale};
+#[cfg(feature = "tls")]
+crate use tls::Tls;
+crate use timings::{Timing, Timings};
+crate use trace::{Recorder, Replayer};
+use version::{Feature, ProxyVersion};
 
 #[derive(Debug, thiserror::Error)]
 crate enum Error {
     #[error("API response {code}, {msg}")]
-    Api { msg: String, code: u16 },
+    Api {
+        msg: String,
+        code: u16,
+        variant: ErrorVariant,
+    },
 
     #[error("Internal error constructing url")]
     Url(#[from] url::ParseError),
@@ -26,25 +53,228 @@ crate enum Error {
 
     #[error(transparent)]
     UnknownIo(#[from] std::io::Error),
+
+    #[error("No cached response for {0} available while offline")]
+    NotCached(Url),
+
+    #[error("Cannot make changes while offline")]
+    Offline,
+
+    #[error("{0}")]
+    Unreachable(String),
+
+    #[error("No {method} {endpoint} request recorded in {}", .dir.display())]
+    NotRecorded {
+        method: String,
+        endpoint: String,
+        dir: std::path::PathBuf,
+    },
+
+    #[error("The proxy version ({version}) does not support {feature}")]
+    Unsupported {
+        feature: Feature,
+        version: ProxyVersion,
+    },
+}
+
+/// The `variant` of an API error response, for the cases we know how to react to
+#[derive(Debug, Clone, PartialEq, Eq)]
+crate enum ErrorVariant {
+    NotFound,
+    AlreadyExists,
+    KeystoreSealed,
+    IncorrectPassphrase,
+    InvalidInput,
+    Unauthorized,
+    Internal,
+    /// A variant this client doesn't know about, or the status text if the body wasn't an
+    /// `ErrorResponse`
+    Unknown(String),
+}
+
+impl ErrorVariant {
+    /// Best guess at the variant for a response body that couldn't be parsed
+    crate fn from_status(code: u16) -> Self {
+        match code {
+            400 | 422 => Self::InvalidInput,
+            401 => Self::Unauthorized,
+            403 => Self::KeystoreSealed,
+            404 => Self::NotFound,
+            409 => Self::AlreadyExists,
+            500 => Self::Internal,
+            _ => Self::Unknown(code.to_string()),
+        }
+    }
+}
+
+impl From<String> for ErrorVariant {
+    fn from(variant: String) -> Self {
+        match variant.as_str() {
+            "NOT_FOUND" => Self::NotFound,
+            "ENTITY_EXISTS" | "IDENTITY_EXISTS" | "PROJECT_EXISTS" => Self::AlreadyExists,
+            "FORBIDDEN" | "KEYSTORE_SEALED" => Self::KeystoreSealed,
+            "INCORRECT_PASSPHRASE" => Self::IncorrectPassphrase,
+            "BAD_REQUEST" | "INVALID_INPUT" | "INVALID_QUERY" | "INVALID_ID" => Self::InvalidInput,
+            "UNAUTHORIZED" => Self::Unauthorized,
+            "INTERNAL_ERROR" => Self::Internal,
+            _ => Self::Unknown(variant),
+        }
+    }
 }
 
 #[derive(Debug)]
 crate struct Api {
     agent: Agent,
+    jobs: NonZeroUsize,
+    version: Mutex<Option<ProxyVersion>>,
 }
 
-impl Api {
+/// Options for connecting to the proxy
+#[derive(Debug, Clone)]
+crate struct Builder {
+    base: Url,
+    jobs: NonZeroUsize,
+    options: agent::Options,
+}
+
+impl Builder {
+    crate fn new(base: Url) -> Self {
+        Self {
+            base,
+            jobs: NonZeroUsize::new(4).unwrap(),
+            options: agent::Options::default(),
+        }
+    }
+
+    /// The maximum number of requests to have in flight at once for bulk operations
+    crate fn jobs(mut self, jobs: NonZeroUsize) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// Cache `GET` responses, to avoid refetching them and to use when the proxy is unreachable
+    crate fn cache(mut self, cache: Cache) -> Self {
+        self.options.cache = Some(cache);
+        self
+    }
+
+    /// Never contact the proxy, all requests will be served from the cache
+    crate fn offline(mut self) -> Self {
+        self.options.offline = true;
+        self
+    }
+
+    /// Like `offline`, but because the proxy couldn't be reached, so responses from the cache are
+    /// reported as stale and anything else fails with `reason`
+    crate fn unreachable(mut self, reason: String) -> Self {
+        self.options.offline = true;
+        self.options.unreachable = Some(reason);
+        self
+    }
+
+    /// How to authenticate requests, defaults to the cookie the proxy expects
+    crate fn auth(mut self, auth: Auth) -> Self {
+        self.options.auth = auth;
+        self
+    }
+
+    /// Write every request and response into a trace directory
+    crate fn record(mut self, recorder: Recorder) -> Self {
+        self.options.recorder = Some(Arc::new(recorder));
+        self
+    }
+
+    /// Serve every request from a trace directory instead of contacting the proxy
+    crate fn replay(mut self, replayer: Replayer) -> Self {
+        self.options.replayer = Some(Arc::new(replayer));
+        self
+    }
+
+    /// Certificates to use when connecting to an `https` base url
+    #[cfg(feature = "tls")]
+    crate fn tls(mut self, tls: Tls) -> Self {
+        self.options.tls = tls;
+        self
+    }
+
+    /// Record how long every request takes
+    crate fn timings(mut self, timings: Timings) -> Self {
+        self.options.timings = Some(timings);
+        self
+    }
+
+    /// Connect to a proxy listening on a unix domain socket, the base url is still used for
+    /// paths and cookies
+    #[cfg(unix)]
+    crate fn socket(mut self, path: std::path::PathBuf) -> Self {
+        self.options.socket = Some(path);
+        self
+    }
+
+    fn build(self, agent: Agent) -> Api {
+        Api {
+            agent,
+            jobs: self.jobs,
+            version: Mutex::new(None),
+        }
+    }
+
     #[fehler::throws(anyhow::Error)]
     /// Sets the current auth token, then returns whether it's valid
-    crate fn with_token(base: Url, auth_token: Secret<String>) -> Option<Self> {
-        Agent::with_token(base, auth_token)?.map(|agent| Self { agent })
+    crate fn with_token(self, auth_token: Secret<String>) -> Option<Api> {
+        let agent = Agent::with_token(self.base.clone(), auth_token, self.options.clone())?;
+        agent.map(|agent| self.build(agent))
     }
 
     #[fehler::throws(anyhow::Error)]
     /// Logs in, then returns the new auth token
-    crate fn with_login(base: Url, passphrase: Secret<String>) -> (Self, Secret<String>) {
-        let (agent, auth_token) = Agent::with_login(base, passphrase)?;
-        (Self { agent }, auth_token)
+    crate fn with_login(self, passphrase: Secret<String>) -> (Api, Secret<String>) {
+        let (agent, auth_token) =
+            Agent::with_login(self.base.clone(), passphrase, self.options.clone())?;
+        (self.build(agent), auth_token)
+    }
+
+    #[fehler::throws(anyhow::Error)]
+    /// Doesn't log in, for use when offline or replaying a trace
+    crate fn without_login(self) -> Api {
+        let agent = Agent::without_login(self.base.clone(), self.options.clone())?;
+        self.build(agent)
+    }
+}
+
+impl Api {
+    crate fn builder(base: Url) -> Builder {
+        Builder::new(base)
+    }
+
+    /// Whether all responses are coming from the cache
+    crate fn is_offline(&self) -> bool {
+        self.agent.is_offline()
+    }
+
+    /// Why the proxy couldn't be reached, if that's why all responses are coming from the cache
+    crate fn unreachable(&self) -> Option<&str> {
+        self.agent.unreachable()
+    }
+
+    /// Whether all responses are coming from a trace
+    crate fn is_replaying(&self) -> bool {
+        self.agent.is_replaying()
+    }
+
+    /// Tracks the responses served from the cache because the proxy was not available, or that
+    /// are older than the cache ttl while offline
+    crate fn stale(&self) -> Stale {
+        self.agent.stale()
+    }
+
+    /// The timings of all requests, if they're being recorded
+    crate fn timings(&self) -> Option<Timings> {
+        self.agent.timings()
+    }
+
+    crate fn avatars(&self) -> avatars::Api<'_> {
+        avatars::Api::new(&self.agent)
     }
 
     crate fn identities(&self) -> identities::Api<'_> {
@@ -58,6 +288,10 @@ impl Api {
     crate fn projects(&self) -> projects::Api<'_> {
         projects::Api::new(&self.agent)
     }
+
+    crate fn raw(&self) -> raw::Api<'_> {
+        raw::Api::new(&self.agent)
+    }
 }
 
 /// For API requests that return no response data
@@ -72,3 +306,34 @@ impl<'de> serde::Deserialize<'de> for Nothing {
         Ok(Nothing)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ErrorVariant;
+
+    #[test]
+    fn variants_from_response() {
+        let variant = |s: &str| ErrorVariant::from(s.to_owned());
+        assert_eq!(variant("NOT_FOUND"), ErrorVariant::NotFound);
+        assert_eq!(variant("PROJECT_EXISTS"), ErrorVariant::AlreadyExists);
+        assert_eq!(variant("FORBIDDEN"), ErrorVariant::KeystoreSealed);
+        assert_eq!(variant("INCORRECT_PASSPHRASE"), ErrorVariant::IncorrectPassphrase);
+        assert_eq!(variant("INVALID_ID"), ErrorVariant::InvalidInput);
+        assert_eq!(variant("UNAUTHORIZED"), ErrorVariant::Unauthorized);
+        assert_eq!(variant("INTERNAL_ERROR"), ErrorVariant::Internal);
+        assert_eq!(variant("not_found"), ErrorVariant::Unknown("not_found".to_owned()));
+        assert_eq!(variant("RATE_LIMITED"), ErrorVariant::Unknown("RATE_LIMITED".to_owned()));
+    }
+
+    #[test]
+    fn variants_from_status() {
+        assert_eq!(ErrorVariant::from_status(400), ErrorVariant::InvalidInput);
+        assert_eq!(ErrorVariant::from_status(422), ErrorVariant::InvalidInput);
+        assert_eq!(ErrorVariant::from_status(401), ErrorVariant::Unauthorized);
+        assert_eq!(ErrorVariant::from_status(403), ErrorVariant::KeystoreSealed);
+        assert_eq!(ErrorVariant::from_status(404), ErrorVariant::NotFound);
+        assert_eq!(ErrorVariant::from_status(409), ErrorVariant::AlreadyExists);
+        assert_eq!(ErrorVariant::from_status(500), ErrorVariant::Internal);
+        assert_eq!(ErrorVariant::from_status(503), ErrorVariant::Unknown("503".to_owned()));
+    }
+}
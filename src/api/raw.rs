@@ -0,0 +1,63 @@
+use crate::api::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+crate enum Method {
+    Get,
+    Post,
+    Put,
+    Patch,
+    Delete,
+}
+
+crate struct Api<'a> {
+    agent: &'a crate::api::Agent,
+}
+
+impl<'a> Api<'a> {
+    pub(super) fn new(agent: &'a crate::api::Agent) -> Self {
+        Self { agent }
+    }
+
+    /// Send a request to any endpoint and return the response body, `path` is relative to the
+    /// base url and may include a query, the response is never cached
+    #[fehler::throws]
+    #[tracing::instrument(skip(self, data))]
+    crate fn request(&self, method: Method, path: &str, data: Option<ureq::SerdeValue>) -> String {
+        self.agent.raw(method.as_str(), path, data)?
+    }
+}
+
+impl Method {
+    crate fn as_str(self) -> &'static str {
+        match self {
+            Self::Get => "GET",
+            Self::Post => "POST",
+            Self::Put => "PUT",
+            Self::Patch => "PATCH",
+            Self::Delete => "DELETE",
+        }
+    }
+}
+
+impl std::str::FromStr for Method {
+    type Err = anyhow::Error;
+
+    #[fehler::throws(Self::Err)]
+    fn from_str(s: &str) -> Self {
+        match s.to_ascii_uppercase().as_str() {
+            "GET" => Self::Get,
+            "POST" => Self::Post,
+            "PUT" => Self::Put,
+            "PATCH" => Self::Patch,
+            "DELETE" => Self::Delete,
+            _ => anyhow::bail!("unsupported method {:?}", s),
+        }
+    }
+}
+
+impl std::fmt::Display for Method {
+    #[fehler::throws(std::fmt::Error)]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) {
+        write!(f, "{}", self.as_str())?;
+    }
+}
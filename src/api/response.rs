@@ -0,0 +1,116 @@
+use std::io::{self, Read};
+
+/// A response from the proxy, a trace or the unix socket, with the body not read yet
+///
+/// ureq can only build a [`ureq::Response`] from a string, which can't hold binary bodies.
+pub(super) struct Response {
+    status: u16,
+    status_text: String,
+    /// Names are lowercase
+    headers: Vec<(String, String)>,
+    body: Box<dyn Read + Send>,
+}
+
+impl Response {
+    pub(super) fn new(
+        status: u16,
+        status_text: String,
+        headers: Vec<(String, String)>,
+        body: impl Read + Send + 'static,
+    ) -> Self {
+        let headers = headers
+            .into_iter()
+            .map(|(name, value)| (name.to_ascii_lowercase(), value))
+            .collect();
+        Self {
+            status,
+            status_text,
+            headers,
+            body: Box::new(body),
+        }
+    }
+
+    pub(super) fn from_ureq(response: ureq::Response) -> Self {
+        let headers = response
+            .headers_names()
+            .into_iter()
+            .flat_map(|name| {
+                response
+                    .all(&name)
+                    .into_iter()
+                    .map(|value| (name.clone(), value.to_owned()))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        let status = response.status();
+        let status_text = response.status_text().to_owned();
+        Self::new(status, status_text, headers, response.into_reader())
+    }
+
+    pub(super) fn status(&self) -> u16 {
+        self.status
+    }
+
+    pub(super) fn status_text(&self) -> &str {
+        &self.status_text
+    }
+
+    /// Every value of the header `name`
+    pub(super) fn all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.headers
+            .iter()
+            .filter(move |(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub(super) fn header(&self, name: &str) -> Option<&str> {
+        self.all(name).next()
+    }
+
+    /// Wraps the body reader, e.g. to observe how it's read
+    pub(super) fn map_body<R: Read + Send + 'static>(
+        self,
+        f: impl FnOnce(Box<dyn Read + Send>) -> R,
+    ) -> Self {
+        Self {
+            body: Box::new(f(self.body)),
+            status: self.status,
+            status_text: self.status_text,
+            headers: self.headers,
+        }
+    }
+
+    /// The body, as it arrives
+    pub(super) fn into_reader(self) -> impl Read + Send {
+        self.body
+    }
+
+    #[fehler::throws(io::Error)]
+    pub(super) fn into_bytes(mut self) -> Vec<u8> {
+        let mut body = Vec::new();
+        self.body.read_to_end(&mut body)?;
+        body
+    }
+
+    #[fehler::throws(io::Error)]
+    pub(super) fn into_string(self) -> String {
+        String::from_utf8(self.into_bytes()?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?
+    }
+
+    #[fehler::throws(io::Error)]
+    pub(super) fn into_json<T: serde::de::DeserializeOwned>(self) -> T {
+        serde_json::from_reader(self.body)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?
+    }
+}
+
+impl std::fmt::Debug for Response {
+    #[fehler::throws(std::fmt::Error)]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) {
+        f.debug_struct("Response")
+            .field("status", &self.status)
+            .field("status_text", &self.status_text)
+            .finish()?;
+    }
+}
@@ -0,0 +1,83 @@
+use std::{
+    io::{self, Read},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// How long a single request to the proxy took
+#[derive(Debug, Clone)]
+crate struct Timing {
+    crate method: String,
+    /// Relative to the base url
+    crate endpoint: String,
+    /// `None` if no response was received
+    crate status: Option<u16>,
+    /// How much of the body was read, `None` if no response was received
+    crate size: Option<u64>,
+    /// Until the body was read completely, or dropped
+    crate duration: Duration,
+}
+
+/// Collects the timing of every request sent to the proxy (or served from a trace)
+#[derive(Debug, Clone, Default)]
+crate struct Timings(Arc<Mutex<Vec<Timing>>>);
+
+impl Timings {
+    pub(super) fn push(&self, timing: Timing) {
+        self.0.lock().unwrap().push(timing);
+    }
+
+    /// Every request so far, in the order they completed
+    crate fn all(&self) -> Vec<Timing> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+/// Counts the bytes of a response body as it's read, and records the timing once it has been
+/// read to the end or dropped
+pub(super) struct Timed<R> {
+    reader: R,
+    timings: Timings,
+    /// Taken when it's been recorded
+    timing: Option<Timing>,
+    start: Instant,
+}
+
+impl<R> Timed<R> {
+    /// `start` is when the request was sent, `timing` already has the status
+    pub(super) fn new(reader: R, timings: Timings, timing: Timing, start: Instant) -> Self {
+        Self {
+            reader,
+            timings,
+            timing: Some(timing),
+            start,
+        }
+    }
+
+    fn finish(&mut self) {
+        if let Some(mut timing) = self.timing.take() {
+            timing.duration = self.start.elapsed();
+            self.timings.push(timing);
+        }
+    }
+}
+
+impl<R: Read> Read for Timed<R> {
+    #[fehler::throws(io::Error)]
+    fn read(&mut self, buf: &mut [u8]) -> usize {
+        let read = self.reader.read(buf)?;
+        if let Some(timing) = &mut self.timing {
+            *timing.size.get_or_insert(0) += read as u64;
+        }
+        if read == 0 && !buf.is_empty() {
+            self.finish();
+        }
+        read
+    }
+}
+
+impl<R> Drop for Timed<R> {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
@@ -0,0 +1,217 @@
+use anyhow::{Context, Error};
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// How to verify the proxy, and identify ourselves to it, when connecting over `https`
+#[derive(Debug, Clone, Default)]
+crate struct Tls {
+    /// PEM file of extra certificate authorities to trust
+    crate ca_cert: Option<PathBuf>,
+    /// PEM file containing the client certificate chain and its private key
+    crate client_cert: Option<PathBuf>,
+    /// Skip verifying the server certificate entirely
+    crate insecure: bool,
+}
+
+struct NoVerification;
+
+impl rustls::ServerCertVerifier for NoVerification {
+    fn verify_server_cert(
+        &self,
+        _roots: &rustls::RootCertStore,
+        _presented_certs: &[rustls::Certificate],
+        _dns_name: webpki::DNSNameRef<'_>,
+        _ocsp_response: &[u8],
+    ) -> Result<rustls::ServerCertVerified, rustls::TLSError> {
+        Ok(rustls::ServerCertVerified::assertion())
+    }
+}
+
+#[fehler::throws]
+fn open(path: &Path) -> BufReader<File> {
+    BufReader::new(File::open(path).with_context(|| format!("Failed to open {}", path.display()))?)
+}
+
+impl Tls {
+    #[fehler::throws]
+    #[tracing::instrument]
+    pub(super) fn config(&self) -> Arc<rustls::ClientConfig> {
+        use rustls::internal::pemfile;
+
+        let mut config = rustls::ClientConfig::new();
+        config
+            .root_store
+            .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
+
+        if let Some(path) = &self.ca_cert {
+            let (valid, invalid) = config
+                .root_store
+                .add_pem_file(&mut open(path)?)
+                .map_err(|()| anyhow::anyhow!("Invalid PEM file {}", path.display()))?;
+            tracing::debug!(valid, invalid, "loaded ca certificates");
+            anyhow::ensure!(valid > 0, "No valid certificates in {}", path.display());
+        }
+
+        if let Some(path) = &self.client_cert {
+            let invalid = || anyhow::anyhow!("Invalid PEM file {}", path.display());
+            let certs = pemfile::certs(&mut open(path)?).map_err(|()| invalid())?;
+            anyhow::ensure!(!certs.is_empty(), "No certificates in {}", path.display());
+            let mut keys = pemfile::pkcs8_private_keys(&mut open(path)?).map_err(|()| invalid())?;
+            if keys.is_empty() {
+                keys = pemfile::rsa_private_keys(&mut open(path)?).map_err(|()| invalid())?;
+            }
+            let key = keys
+                .into_iter()
+                .next()
+                .with_context(|| format!("No private key in {}", path.display()))?;
+            config
+                .set_single_client_cert(certs, key)
+                .with_context(|| format!("Invalid client certificate {}", path.display()))?;
+        }
+
+        if self.insecure {
+            config
+                .dangerous()
+                .set_certificate_verifier(Arc::new(NoVerification));
+        }
+
+        Arc::new(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Tls;
+    use rustls::internal::pemfile;
+    use std::{
+        io::{BufReader, Read, Write},
+        net::TcpListener,
+        path::PathBuf,
+        sync::Arc,
+    };
+
+    fn data(name: &str) -> PathBuf {
+        [env!("CARGO_MANIFEST_DIR"), "tests", "data", "tls", name]
+            .iter()
+            .collect()
+    }
+
+    fn read(name: &str) -> BufReader<std::fs::File> {
+        BufReader::new(std::fs::File::open(data(name)).unwrap())
+    }
+
+    /// Serves a single `{}` response over https on localhost, signed by the test CA, optionally
+    /// only to clients with a certificate signed by it too
+    fn serve(client_auth: bool) -> (u16, std::thread::JoinHandle<()>) {
+        let verifier = if client_auth {
+            let mut roots = rustls::RootCertStore::empty();
+            roots.add_pem_file(&mut read("ca.pem")).unwrap();
+            rustls::AllowAnyAuthenticatedClient::new(roots)
+        } else {
+            rustls::NoClientAuth::new()
+        };
+        let mut config = rustls::ServerConfig::new(verifier);
+        let certs = pemfile::certs(&mut read("server.pem")).unwrap();
+        let key = pemfile::pkcs8_private_keys(&mut read("server.pem")).unwrap().remove(0);
+        config.set_single_cert(certs, key).unwrap();
+        let config = Arc::new(config);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (socket, _) = listener.accept().unwrap();
+            let session = rustls::ServerSession::new(&config);
+            let mut stream = rustls::StreamOwned::new(session, socket);
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                match stream.read(&mut buf) {
+                    Ok(0) | Err(_) => return,
+                    Ok(n) => request.extend_from_slice(&buf[..n]),
+                }
+            }
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}");
+            let _ = stream.flush();
+        });
+        (port, server)
+    }
+
+    #[fehler::throws(anyhow::Error)]
+    fn get(tls: &Tls, client_auth: bool) -> String {
+        let (port, server) = serve(client_auth);
+        let agent = ureq::builder().tls_config(tls.config()?).build();
+        let result = agent
+            .get(&format!("https://localhost:{}/", port))
+            .call()
+            .map_err(anyhow::Error::from)
+            .and_then(|response| Ok(response.into_string()?));
+        server.join().unwrap();
+        result?
+    }
+
+    #[test]
+    fn rejects_unknown_ca() {
+        assert!(get(&Tls::default(), false).is_err());
+    }
+
+    #[test]
+    fn trusts_ca_cert() {
+        let tls = Tls {
+            ca_cert: Some(data("ca.pem")),
+            ..Tls::default()
+        };
+        assert_eq!(get(&tls, false).unwrap(), "{}");
+    }
+
+    #[test]
+    fn insecure_skips_verification() {
+        let tls = Tls {
+            insecure: true,
+            ..Tls::default()
+        };
+        assert_eq!(get(&tls, false).unwrap(), "{}");
+    }
+
+    #[test]
+    fn sends_client_cert() {
+        let tls = Tls {
+            ca_cert: Some(data("ca.pem")),
+            client_cert: Some(data("client.pem")),
+            ..Tls::default()
+        };
+        assert_eq!(get(&tls, true).unwrap(), "{}");
+    }
+
+    #[test]
+    fn client_cert_required() {
+        let tls = Tls {
+            ca_cert: Some(data("ca.pem")),
+            ..Tls::default()
+        };
+        assert!(get(&tls, true).is_err());
+    }
+
+    #[test]
+    fn client_cert_needs_key() {
+        let tls = Tls {
+            client_cert: Some(data("ca.pem")),
+            ..Tls::default()
+        };
+        let err = tls.config().unwrap_err();
+        assert!(err.to_string().starts_with("No private key in"), "{}", err);
+    }
+
+    #[test]
+    fn ca_cert_must_exist() {
+        let tls = Tls {
+            ca_cert: Some(data("missing.pem")),
+            ..Tls::default()
+        };
+        let err = tls.config().unwrap_err();
+        assert!(err.to_string().starts_with("Failed to open"), "{}", err);
+    }
+}
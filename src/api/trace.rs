@@ -0,0 +1,273 @@
+use crate::api::{response::Response, Error};
+use anyhow::Context;
+use std::{
+    io::Cursor,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+const REDACTED: &str = "<redacted>";
+
+/// The headers from responses that are kept in traces
+const RECORDED_HEADERS: &[&str] = &["content-type", "set-cookie"];
+
+/// A single request and its response, as stored in a trace directory
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+struct Exchange {
+    method: String,
+    /// Relative to the base url, so traces can be replayed against any base
+    endpoint: String,
+    request: Option<ureq::SerdeValue>,
+    status: u16,
+    status_text: String,
+    headers: Vec<(String, String)>,
+    response: String,
+}
+
+/// Writes every exchange with the proxy into a directory, with secrets redacted
+#[derive(Debug)]
+crate struct Recorder {
+    dir: PathBuf,
+    count: AtomicUsize,
+}
+
+/// Serves the exchanges from a directory written by a [`Recorder`] instead of contacting a proxy
+#[derive(Debug)]
+crate struct Replayer {
+    dir: PathBuf,
+    exchanges: Mutex<Vec<Exchange>>,
+}
+
+impl Recorder {
+    #[fehler::throws(anyhow::Error)]
+    crate fn new(dir: PathBuf) -> Self {
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create trace directory {}", dir.display()))?;
+        Self {
+            dir,
+            count: AtomicUsize::new(0),
+        }
+    }
+
+    /// Records the exchange, then rebuilds the response from the recorded data (since the body
+    /// has been consumed)
+    #[fehler::throws(Error)]
+    #[tracing::instrument(skip(self, request, response))]
+    pub(super) fn record(
+        &self,
+        method: &str,
+        endpoint: &str,
+        request: Option<&ureq::SerdeValue>,
+        response: Response,
+    ) -> Response {
+        let status = response.status();
+        let status_text = response.status_text().to_owned();
+        let headers: Vec<(String, String)> = RECORDED_HEADERS
+            .iter()
+            .flat_map(|&name| {
+                response
+                    .all(name)
+                    .map(move |value| (name.to_owned(), value.to_owned()))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        let body = response.into_bytes()?;
+
+        let exchange = Exchange {
+            method: method.to_owned(),
+            endpoint: endpoint.to_owned(),
+            request: request.cloned().map(redact_request),
+            status,
+            status_text,
+            headers: headers.iter().cloned().map(redact_header).collect(),
+            // Traces are for inspecting the JSON api, binary bodies can't be replayed exactly
+            response: String::from_utf8_lossy(&body).into_owned(),
+        };
+        let index = self.count.fetch_add(1, Ordering::SeqCst);
+        let path = self.dir.join(format!("{:04}.json", index));
+        std::fs::write(&path, serde_json::to_vec_pretty(&exchange)?)?;
+        tracing::debug!(path = %path.display(), "recorded exchange");
+
+        // Rebuild from the unredacted headers so that login still works while recording
+        Response::new(status, exchange.status_text, headers, Cursor::new(body))
+    }
+}
+
+impl Replayer {
+    #[fehler::throws(anyhow::Error)]
+    crate fn new(dir: PathBuf) -> Self {
+        let mut paths = std::fs::read_dir(&dir)
+            .with_context(|| format!("Failed to read trace directory {}", dir.display()))?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<Result<Vec<_>, std::io::Error>>()?;
+        paths.retain(|path| path.extension().map_or(false, |ext| ext == "json"));
+        paths.sort();
+        let exchanges = paths
+            .iter()
+            .map(|path| {
+                let data = std::fs::read(path)?;
+                serde_json::from_slice(&data)
+                    .with_context(|| format!("Invalid trace file {}", path.display()))
+            })
+            .collect::<Result<_, anyhow::Error>>()?;
+        Self {
+            dir,
+            exchanges: Mutex::new(exchanges),
+        }
+    }
+
+    /// Takes the first unused exchange matching the request, the last matching exchange is kept
+    /// so that repeated requests keep working
+    #[fehler::throws(Error)]
+    #[tracing::instrument(skip(self))]
+    pub(super) fn replay(&self, method: &str, endpoint: &str) -> Response {
+        let mut exchanges = self.exchanges.lock().unwrap();
+        let matching: Vec<usize> = exchanges
+            .iter()
+            .enumerate()
+            .filter(|(_, exchange)| exchange.method == method && exchange.endpoint == endpoint)
+            .map(|(index, _)| index)
+            .collect();
+        let exchange = match matching.as_slice() {
+            [] => fehler::throw!(Error::NotRecorded {
+                method: method.to_owned(),
+                endpoint: endpoint.to_owned(),
+                dir: self.dir.clone(),
+            }),
+            &[index] => exchanges[index].clone(),
+            &[index, ..] => exchanges.remove(index),
+        };
+        Response::new(
+            exchange.status,
+            exchange.status_text,
+            exchange.headers,
+            Cursor::new(exchange.response.into_bytes()),
+        )
+    }
+}
+
+fn redact_request(mut request: ureq::SerdeValue) -> ureq::SerdeValue {
+    if let Some(passphrase) = request.get_mut("passphrase") {
+        *passphrase = REDACTED.into();
+    }
+    request
+}
+
+fn redact_header((name, value): (String, String)) -> (String, String) {
+    let value = if name == "set-cookie" {
+        match cookie::Cookie::parse(value) {
+            Ok(mut cookie) => {
+                cookie.set_value(REDACTED);
+                cookie.to_string()
+            }
+            Err(_) => REDACTED.to_owned(),
+        }
+    } else {
+        value
+    };
+    (name, value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Exchange, Recorder, Replayer, REDACTED};
+    use crate::api::{response::Response, Error};
+    use std::{io::Cursor, path::PathBuf};
+
+    fn trace_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "radicle-cli-test-trace-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn response(headers: &[(&str, &str)], body: &str) -> Response {
+        let headers = headers
+            .iter()
+            .map(|&(name, value)| (name.to_owned(), value.to_owned()))
+            .collect();
+        Response::new(200, "OK".to_owned(), headers, Cursor::new(body.to_owned()))
+    }
+
+    #[test]
+    fn redacts_secrets() {
+        let dir = trace_dir("redacts");
+        let recorder = Recorder::new(dir.clone()).unwrap();
+        let request = serde_json::json!({ "passphrase": "hunter2" });
+        let headers = [
+            ("Content-Type", "application/json"),
+            ("Set-Cookie", "auth-token=s3cr3t; Path=/"),
+        ];
+        let response = recorder
+            .record(
+                "POST",
+                "v1/keystore/unseal",
+                Some(&request),
+                response(&headers, "null"),
+            )
+            .unwrap();
+        assert_eq!(response.header("set-cookie"), Some("auth-token=s3cr3t; Path=/"));
+
+        let recorded = std::fs::read_to_string(dir.join("0000.json")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(!recorded.contains("hunter2"), "{}", recorded);
+        assert!(!recorded.contains("s3cr3t"), "{}", recorded);
+
+        let exchange: Exchange = serde_json::from_str(&recorded).unwrap();
+        assert_eq!(exchange.request, Some(serde_json::json!({ "passphrase": REDACTED })));
+        let (_, cookie) = exchange
+            .headers
+            .iter()
+            .find(|(name, _)| name == "set-cookie")
+            .unwrap();
+        let cookie = cookie::Cookie::parse(cookie.as_str()).unwrap();
+        assert_eq!((cookie.name(), cookie.value()), ("auth-token", REDACTED));
+        assert_eq!(cookie.path(), Some("/"));
+        assert!(exchange
+            .headers
+            .contains(&("content-type".to_owned(), "application/json".to_owned())));
+    }
+
+    #[test]
+    fn replays_in_order_and_keeps_the_last() {
+        let dir = trace_dir("replays");
+        let recorder = Recorder::new(dir.clone()).unwrap();
+        for body in &["1", "2", "3"] {
+            recorder
+                .record("GET", "v1/identities", None, response(&[], body))
+                .unwrap();
+        }
+        recorder
+            .record("GET", "v1/session", None, response(&[], "4"))
+            .unwrap();
+
+        let replayer = Replayer::new(dir.clone()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let replay = |endpoint: &str| {
+            replayer
+                .replay("GET", endpoint)
+                .unwrap()
+                .into_string()
+                .unwrap()
+        };
+        assert_eq!(replay("v1/identities"), "1");
+        assert_eq!(replay("v1/session"), "4");
+        assert_eq!(replay("v1/identities"), "2");
+        assert_eq!(replay("v1/identities"), "3");
+        assert_eq!(replay("v1/identities"), "3");
+        assert_eq!(replay("v1/session"), "4");
+
+        match replayer.replay("POST", "v1/identities") {
+            Err(Error::NotRecorded { method, endpoint, .. }) => {
+                assert_eq!((method.as_str(), endpoint.as_str()), ("POST", "v1/identities"))
+            }
+            other => panic!("expected NotRecorded, got {:?}", other),
+        }
+    }
+}
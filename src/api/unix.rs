@@ -0,0 +1,352 @@
+use crate::api::{response::Response, Error};
+use secrecy::{ExposeSecret, Secret};
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    sync::Mutex,
+};
+use url::Url;
+
+/// Talks HTTP/1.1 to a proxy listening on a unix domain socket, which ureq can't connect to
+///
+/// Each request uses a new connection, the proxy is local so there's little to gain from
+/// keeping them alive.
+pub(super) struct Socket {
+    path: PathBuf,
+    /// Keeps the same cookie semantics as the ureq agent uses over TCP
+    cookies: Mutex<cookie_store::CookieStore>,
+}
+
+impl Socket {
+    pub(super) fn new(path: PathBuf, cookies: cookie_store::CookieStore) -> Self {
+        Self {
+            path,
+            cookies: Mutex::new(cookies),
+        }
+    }
+
+    pub(super) fn cookie(&self, domain: &str, name: &str) -> Option<Secret<String>> {
+        let cookies = self.cookies.lock().unwrap();
+        let cookie = cookies.get(domain, "/", name)?;
+        Some(Secret::new(cookie.value().to_owned()))
+    }
+
+    #[fehler::throws]
+    #[tracing::instrument(skip(headers, data))]
+    pub(super) fn send(
+        &self,
+        method: &str,
+        url: &Url,
+        headers: &[(String, Secret<String>)],
+        data: Option<&ureq::SerdeValue>,
+    ) -> Response {
+        let body = match data {
+            Some(data) => serde_json::to_vec(data)?,
+            None => Vec::new(),
+        };
+
+        let mut head = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nContent-Length: {}\r\n",
+            method,
+            &url[url::Position::BeforePath..],
+            url.host_str().unwrap_or("localhost"),
+            body.len(),
+        );
+        if data.is_some() {
+            head += "Content-Type: application/json\r\n";
+        }
+        for (name, value) in headers {
+            head += &format!("{}: {}\r\n", name, value.expose_secret());
+        }
+        let cookies = self
+            .cookies
+            .lock()
+            .unwrap()
+            .get_request_cookies(url)
+            .map(|cookie| format!("{}={}", cookie.name(), cookie.value()))
+            .collect::<Vec<_>>();
+        if !cookies.is_empty() {
+            head += &format!("Cookie: {}\r\n", cookies.join("; "));
+        }
+        head += "\r\n";
+        let head = Secret::new(head);
+
+        let response = self
+            .exchange(head.expose_secret().as_bytes(), &body)
+            .map_err(|err| Box::new(ureq::Error::from(err)))?;
+
+        let set_cookies = response
+            .all("set-cookie")
+            .filter_map(|value| cookie::Cookie::parse(value.to_owned()).ok())
+            .map(|cookie| cookie.into_owned())
+            .collect::<Vec<_>>();
+        self.cookies
+            .lock()
+            .unwrap()
+            .store_response_cookies(set_cookies.into_iter(), url);
+
+        response
+    }
+
+    #[fehler::throws(io::Error)]
+    fn exchange(&self, head: &[u8], body: &[u8]) -> Response {
+        let mut stream = UnixStream::connect(&self.path)?;
+        stream.write_all(head)?;
+        stream.write_all(body)?;
+        parse(BufReader::new(stream))?
+    }
+}
+
+/// Reads the status line and headers, leaving the body to be read from the returned response
+#[fehler::throws(io::Error)]
+fn parse(mut reader: impl BufRead + Send + 'static) -> Response {
+    let status_line = read_line(&mut reader)?;
+    let mut status_line = status_line.splitn(3, ' ');
+    let _version = status_line.next();
+    let status = status_line
+        .next()
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| invalid("invalid status line"))?;
+    let status_text = status_line.next().unwrap_or_default().to_owned();
+
+    let mut headers = Vec::new();
+    loop {
+        let line = read_line(&mut reader)?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some(index) = line.find(':') {
+            let (name, value) = (&line[..index], &line[index + 1..]);
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_owned()));
+        }
+    }
+
+    let chunked = headers
+        .iter()
+        .any(|(name, value)| name == "transfer-encoding" && value.contains("chunked"));
+    let length = headers
+        .iter()
+        .find(|(name, _)| name == "content-length")
+        .and_then(|(_, length)| length.parse::<u64>().ok());
+    // Every request asks for the connection to be closed, so otherwise the body ends with it
+    let body: Box<dyn Read + Send> = match (chunked, length) {
+        (true, _) => Box::new(Dechunk::new(reader)),
+        (false, Some(length)) => Box::new(reader.take(length)),
+        (false, None) => Box::new(reader),
+    };
+    Response::new(status, status_text, headers, body)
+}
+
+/// A line of the response head, without the line ending
+#[fehler::throws(io::Error)]
+fn read_line(reader: &mut impl BufRead) -> String {
+    let mut line = Vec::new();
+    reader.read_until(b'\n', &mut line)?;
+    if !line.ends_with(b"\r\n") {
+        fehler::throw!(invalid("missing end of headers"));
+    }
+    line.truncate(line.len() - 2);
+    String::from_utf8(line).map_err(|_| invalid("non-utf8 headers"))?
+}
+
+/// Decodes a `Transfer-Encoding: chunked` body as it's read
+struct Dechunk<R> {
+    reader: R,
+    /// What's left of the current chunk
+    remaining: u64,
+    done: bool,
+}
+
+impl<R: BufRead> Dechunk<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader,
+            remaining: 0,
+            done: false,
+        }
+    }
+
+    #[fehler::throws(io::Error)]
+    fn next_chunk(&mut self) {
+        let mut line = Vec::new();
+        self.reader.read_until(b'\n', &mut line)?;
+        if !line.ends_with(b"\r\n") {
+            fehler::throw!(invalid("truncated chunk"));
+        }
+        // Chunk extensions follow the size after a `;`, nothing uses them
+        self.remaining = std::str::from_utf8(&line[..line.len() - 2])
+            .ok()
+            .and_then(|line| line.split(';').next())
+            .and_then(|size| u64::from_str_radix(size.trim(), 16).ok())
+            .ok_or_else(|| invalid("invalid chunk size"))?;
+        self.done = self.remaining == 0;
+    }
+}
+
+impl<R: BufRead> Read for Dechunk<R> {
+    #[fehler::throws(io::Error)]
+    fn read(&mut self, buf: &mut [u8]) -> usize {
+        if self.remaining == 0 && !self.done {
+            self.next_chunk()?;
+        }
+        if self.done || buf.is_empty() {
+            0
+        } else {
+            let max = buf.len().min(self.remaining as usize);
+            let read = self.reader.read(&mut buf[..max])?;
+            if read == 0 {
+                fehler::throw!(invalid("truncated chunk"));
+            }
+            self.remaining -= read as u64;
+            if self.remaining == 0 {
+                let mut end = [0; 2];
+                self.reader
+                    .read_exact(&mut end)
+                    .map_err(|_| invalid("truncated chunk"))?;
+                if &end != b"\r\n" {
+                    fehler::throw!(invalid("invalid chunk end"));
+                }
+            }
+            read
+        }
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid response from proxy socket: {}", msg),
+    )
+}
+
+impl std::fmt::Debug for Socket {
+    #[fehler::throws(std::fmt::Error)]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) {
+        f.debug_struct("Socket").field("path", &self.path).finish()?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, Socket};
+    use secrecy::ExposeSecret;
+    use std::{
+        io::{BufRead, BufReader, Cursor, Write},
+        os::unix::net::UnixListener,
+    };
+
+    fn body(raw: &[u8]) -> std::io::Result<Vec<u8>> {
+        parse(Cursor::new(raw.to_vec()))?.into_bytes()
+    }
+
+    #[test]
+    fn parses_head() {
+        let raw = b"HTTP/1.1 404 Not Found\r\nContent-Type: application/json\r\n\
+            Set-Cookie: a=1\r\nset-cookie: b=2\r\nContent-Length: 2\r\n\r\n{}";
+        let response = parse(Cursor::new(raw.to_vec())).unwrap();
+        assert_eq!(response.status(), 404);
+        assert_eq!(response.status_text(), "Not Found");
+        assert_eq!(response.header("content-type"), Some("application/json"));
+        assert_eq!(response.all("set-cookie").collect::<Vec<_>>(), ["a=1", "b=2"]);
+        assert_eq!(response.into_string().unwrap(), "{}");
+    }
+
+    #[test]
+    fn body_ends_at_content_length() {
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}trailing";
+        assert_eq!(body(raw).unwrap(), b"{}");
+    }
+
+    #[test]
+    fn body_without_length_ends_with_connection() {
+        let raw = b"HTTP/1.1 200 OK\r\n\r\n{}";
+        assert_eq!(body(raw).unwrap(), b"{}");
+    }
+
+    #[test]
+    fn keeps_binary_body() {
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\n\x89PN\xff";
+        assert_eq!(body(raw).unwrap(), b"\x89PN\xff");
+    }
+
+    #[test]
+    fn rejects_truncated_head() {
+        assert!(body(b"HTTP/1.1 200 OK\r\nContent-Length: 2").is_err());
+        assert!(body(b"HTTP/1.1 OK\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn dechunks() {
+        let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+            4\r\nWiki\r\n5\r\npedia\r\n0\r\n\r\n";
+        assert_eq!(body(raw).unwrap(), b"Wikipedia");
+    }
+
+    #[test]
+    fn dechunk_ignores_extensions() {
+        let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+            4;name=value\r\nWiki\r\n0;last\r\n\r\n";
+        assert_eq!(body(raw).unwrap(), b"Wiki");
+    }
+
+    #[test]
+    fn dechunk_rejects_truncated() {
+        let header = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n";
+        for chunks in &["4\r\nWi", "4\r\nWiki", "4\r\nWiki\r\n", "4\r\nWiki\r\n0", "zz\r\n"] {
+            let raw = format!("{}{}", header, chunks);
+            assert!(body(raw.as_bytes()).is_err(), "{:?}", chunks);
+        }
+    }
+
+    #[test]
+    fn keeps_cookies_between_requests() {
+        let path =
+            std::env::temp_dir().join(format!("radicle-cli-test-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+
+        // Stands in for the proxy, only accepting requests with the cookie it set on unseal
+        let server = std::thread::spawn(move || {
+            let mut heads = Vec::new();
+            for _ in 0..2 {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut head = String::new();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                while !head.ends_with("\r\n\r\n") {
+                    if reader.read_line(&mut head).unwrap() == 0 {
+                        break;
+                    }
+                }
+                let response: &[u8] = if head.starts_with("POST /v1/keystore/unseal ") {
+                    b"HTTP/1.1 200 OK\r\nSet-Cookie: auth-token=secret; Path=/\r\n\
+                      Content-Length: 2\r\n\r\n{}"
+                } else if head.contains("\r\nCookie: auth-token=secret\r\n") {
+                    b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\n[]\r\n0\r\n\r\n"
+                } else {
+                    b"HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\n\r\n"
+                };
+                stream.write_all(response).unwrap();
+                heads.push(head);
+            }
+            heads
+        });
+
+        let cookies = cookie_store::CookieStore::load_json(Cursor::new("")).unwrap();
+        let socket = Socket::new(path.clone(), cookies);
+        let url = "http://localhost/v1/keystore/unseal".parse().unwrap();
+        let response = socket.send("POST", &url, &[], None).unwrap();
+        assert_eq!(response.status(), 200);
+        let token = socket.cookie("localhost", "auth-token").unwrap();
+        assert_eq!(token.expose_secret(), "secret");
+
+        let url = "http://localhost/v1/identities".parse().unwrap();
+        let response = socket.send("GET", &url, &[], None).unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.into_string().unwrap(), "[]");
+
+        let heads = server.join().unwrap();
+        assert!(!heads[0].contains("Cookie:"), "{}", heads[0]);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
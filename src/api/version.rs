@@ -0,0 +1,278 @@
+use crate::api::{Api, Error};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+crate struct Version {
+    crate major: u64,
+    crate minor: u64,
+    crate patch: u64,
+}
+
+/// Parts of the API that are not available in every proxy version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+crate enum Feature {
+    /// `/v1/session/settings`, used to manage seeds
+    SessionSettings,
+    /// `/v1/projects/requests`
+    ProjectRequests,
+    /// `/v1/projects/<urn>/peers`
+    ProjectPeers,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+crate enum ProxyVersion {
+    /// The version the proxy reported about itself
+    Reported(Version),
+    /// The proxy didn't report a version, but has the endpoints of this range
+    Inferred { min: Version, before: Version },
+    /// The proxy reported a version that isn't in the compatibility table, or it didn't report
+    /// one and none of the probes matched
+    Unknown(Option<Version>),
+    /// Detection failed, e.g. because we're offline
+    Undetected,
+}
+
+/// A range of proxy versions with the same API
+struct Compatibility {
+    min: Version,
+    /// The first version after the range
+    before: Version,
+    features: &'static [Feature],
+    /// An endpoint that older proxies don't have, to recognise the range when the proxy doesn't
+    /// report its version, the oldest range needs none
+    probe: Option<&'static [&'static str]>,
+}
+
+const fn version(major: u64, minor: u64, patch: u64) -> Version {
+    Version {
+        major,
+        minor,
+        patch,
+    }
+}
+
+/// The proxy versions this client is known to work with, newest first, see the README for the
+/// latest tested
+const COMPATIBILITY: &[Compatibility] = &[
+    Compatibility {
+        min: version(0, 1, 0),
+        before: version(0, 1, 5),
+        features: &[
+            Feature::SessionSettings,
+            Feature::ProjectRequests,
+            Feature::ProjectPeers,
+        ],
+        probe: Some(&["v1", "projects", "requests"]),
+    },
+    // Project requests and the peers of a project only came with 0.1
+    Compatibility {
+        min: version(0, 0, 0),
+        before: version(0, 1, 0),
+        features: &[Feature::SessionSettings],
+        probe: None,
+    },
+];
+
+impl ProxyVersion {
+    fn compatibility(&self) -> Option<&'static Compatibility> {
+        match *self {
+            Self::Reported(version) => COMPATIBILITY
+                .iter()
+                .find(|compat| compat.min <= version && version < compat.before),
+            Self::Inferred { min, .. } => COMPATIBILITY.iter().find(|compat| compat.min == min),
+            Self::Unknown(_) | Self::Undetected => None,
+        }
+    }
+
+    /// Whether the proxy is known to support `feature`, `None` if we don't know the version
+    crate fn supports(&self, feature: Feature) -> Option<bool> {
+        self.compatibility()
+            .map(|compat| compat.features.contains(&feature))
+    }
+}
+
+impl Api {
+    /// Detects the proxy version, first by asking it then by probing which endpoints it has, the
+    /// result is remembered for the lifetime of the `Api`
+    #[tracing::instrument(skip(self))]
+    crate fn proxy_version(&self) -> ProxyVersion {
+        let mut detected = self.version.lock().unwrap();
+        if let Some(version) = *detected {
+            return version;
+        }
+        let version = self.detect_version().unwrap_or_else(|err| {
+            tracing::debug!(%err, "failed to detect proxy version");
+            ProxyVersion::Undetected
+        });
+        tracing::debug!(?version);
+        *detected = Some(version);
+        version
+    }
+
+    #[fehler::throws]
+    fn detect_version(&self) -> ProxyVersion {
+        let diagnostics: Option<ureq::SerdeValue> = self.agent.get_opt(["v1", "diagnostics"])?;
+        let reported = diagnostics
+            .as_ref()
+            .and_then(|diagnostics| diagnostics.get("version")?.as_str()?.parse().ok());
+        if let Some(version) = reported {
+            let reported = ProxyVersion::Reported(version);
+            return if reported.compatibility().is_some() {
+                reported
+            } else {
+                ProxyVersion::Unknown(Some(version))
+            };
+        }
+
+        for compat in COMPATIBILITY {
+            let matches = match compat.probe {
+                Some(probe) => self.has_endpoint(probe)?,
+                None => true,
+            };
+            if matches {
+                return ProxyVersion::Inferred {
+                    min: compat.min,
+                    before: compat.before,
+                };
+            }
+        }
+        ProxyVersion::Unknown(None)
+    }
+
+    /// Whether a GET of `path` succeeds, proxies reject paths they don't know with 404 or, when it
+    /// looks like an id, 400
+    #[fehler::throws]
+    fn has_endpoint(&self, path: &[&str]) -> bool {
+        match self.agent.get::<ureq::SerdeValue>(path.to_vec()) {
+            Ok(_) => true,
+            Err(Error::Api { .. }) => false,
+            Err(err) => fehler::throw!(err),
+        }
+    }
+
+    /// Fails if the proxy is known not to support `feature`, unknown versions are allowed to try
+    #[fehler::throws]
+    crate fn require(&self, feature: Feature) {
+        let version = self.proxy_version();
+        if version.supports(feature) == Some(false) {
+            fehler::throw!(Error::Unsupported { feature, version });
+        }
+    }
+}
+
+impl FromStr for Version {
+    type Err = std::num::ParseIntError;
+
+    #[fehler::throws(Self::Err)]
+    fn from_str(s: &str) -> Self {
+        let s = s.trim_start_matches('v');
+        // Ignore any pre-release or build metadata
+        let s = s.split(|c| c == '-' || c == '+').next().unwrap_or(s);
+        let mut parts = s.splitn(3, '.');
+        let mut next = || parts.next().unwrap_or("0").parse::<u64>();
+        Self {
+            major: next()?,
+            minor: next()?,
+            patch: next()?,
+        }
+    }
+}
+
+impl std::fmt::Display for Version {
+    #[fehler::throws(std::fmt::Error)]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+    }
+}
+
+impl std::fmt::Display for ProxyVersion {
+    #[fehler::throws(std::fmt::Error)]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) {
+        match self {
+            Self::Reported(version) => write!(f, "{}", version)?,
+            Self::Inferred { min, before } => {
+                write!(f, "unreported, at least {} but before {}", min, before)?
+            }
+            Self::Unknown(Some(version)) => write!(f, "{} (unknown)", version)?,
+            Self::Unknown(None) => write!(f, "unknown")?,
+            Self::Undetected => write!(f, "undetected")?,
+        }
+    }
+}
+
+impl std::fmt::Display for Feature {
+    #[fehler::throws(std::fmt::Error)]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) {
+        match self {
+            Self::SessionSettings => write!(f, "session settings")?,
+            Self::ProjectRequests => write!(f, "project requests")?,
+            Self::ProjectPeers => write!(f, "project peers")?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{version, Feature, ProxyVersion, Version};
+
+    #[test]
+    fn parses_versions() {
+        assert_eq!("0.1.4".parse::<Version>().unwrap(), version(0, 1, 4));
+        assert_eq!("v1.2.3".parse::<Version>().unwrap(), version(1, 2, 3));
+        assert_eq!("0.1".parse::<Version>().unwrap(), version(0, 1, 0));
+        assert_eq!("0.1.2-beta.1+abc".parse::<Version>().unwrap(), version(0, 1, 2));
+        assert_eq!("0.1.2+abc".parse::<Version>().unwrap(), version(0, 1, 2));
+    }
+
+    #[test]
+    fn rejects_invalid_versions() {
+        assert!("".parse::<Version>().is_err());
+        assert!("one.two".parse::<Version>().is_err());
+        assert!("0.1.x".parse::<Version>().is_err());
+    }
+
+    #[test]
+    fn versions_order_numerically() {
+        assert!(version(0, 1, 10) > version(0, 1, 9));
+        assert!(version(0, 2, 0) > version(0, 1, 99));
+    }
+
+    #[test]
+    fn reported_versions_support_their_features() {
+        let current = ProxyVersion::Reported(version(0, 1, 4));
+        assert_eq!(current.supports(Feature::SessionSettings), Some(true));
+        assert_eq!(current.supports(Feature::ProjectRequests), Some(true));
+        assert_eq!(current.supports(Feature::ProjectPeers), Some(true));
+
+        let old = ProxyVersion::Reported(version(0, 0, 12));
+        assert_eq!(old.supports(Feature::SessionSettings), Some(true));
+        assert_eq!(old.supports(Feature::ProjectRequests), Some(false));
+        assert_eq!(old.supports(Feature::ProjectPeers), Some(false));
+    }
+
+    #[test]
+    fn inferred_versions_support_their_range() {
+        let current = ProxyVersion::Inferred {
+            min: version(0, 1, 0),
+            before: version(0, 1, 5),
+        };
+        assert_eq!(current.supports(Feature::ProjectRequests), Some(true));
+
+        let old = ProxyVersion::Inferred {
+            min: version(0, 0, 0),
+            before: version(0, 1, 0),
+        };
+        assert_eq!(old.supports(Feature::ProjectRequests), Some(false));
+    }
+
+    #[test]
+    fn unknown_versions_support_nothing_for_sure() {
+        for version in &[
+            ProxyVersion::Unknown(Some(version(0, 2, 0))),
+            ProxyVersion::Unknown(None),
+            ProxyVersion::Undetected,
+        ] {
+            assert_eq!(version.supports(Feature::ProjectPeers), None);
+        }
+    }
+}
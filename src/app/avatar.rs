@@ -0,0 +1,55 @@
+use crate::api::identities::AvatarFallback;
+use crate::app::{color::ColorSupport, WithContext};
+use anyhow::{Context, Error};
+use std::{io::Write, path::Path};
+
+/// Renders the avatar the same way as the GUI does, the emoji centered on a circle of the
+/// background colour
+fn svg(avatar: &AvatarFallback) -> String {
+    let background = &avatar.background;
+    format!(
+        concat!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="64" height="64" viewBox="0 0 64 64">"#,
+            "\n",
+            r#"  <circle cx="32" cy="32" r="32" fill="rgb({}, {}, {})"/>"#,
+            "\n",
+            r#"  <text x="32" y="32" font-size="32" text-anchor="middle" dominant-baseline="central">{}</text>"#,
+            "\n",
+            "</svg>\n",
+        ),
+        background.r, background.g, background.b, avatar.emoji,
+    )
+}
+
+/// A small tile of the background colour with the emoji in the middle
+fn preview(avatar: &AvatarFallback, color: ColorSupport) -> String {
+    if color == ColorSupport::None {
+        return avatar.emoji.clone();
+    }
+    // Emoji are two cells wide, so every row is the same width
+    let blank = color.on_background("      ", &avatar.background);
+    let middle = color.on_background(&format!("  {}  ", avatar.emoji), &avatar.background);
+    format!("{}\n{}\n{}", blank, middle, blank)
+}
+
+impl<T> WithContext<T> {
+    #[fehler::throws]
+    /// Writes the avatar as an SVG to `path`, or to the output if it's not a terminal, otherwise
+    /// shows a preview of it
+    pub(super) fn write_avatar(&self, avatar: &AvatarFallback, path: Option<&Path>) {
+        match path {
+            Some(path) => {
+                anyhow::ensure!(
+                    path.extension().map_or(true, |extension| extension == "svg"),
+                    "Avatars can only be saved as SVG, the proxy only provides the emoji and colour"
+                );
+                std::fs::write(path, svg(avatar))
+                    .with_context(|| format!("Failed to write avatar to {}", path.display()))?;
+            }
+            None if atty::is(atty::Stream::Stdout) => {
+                writeln!(self.output(), "{}", preview(avatar, self.color()))?;
+            }
+            None => write!(self.output(), "{}", svg(avatar))?,
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use crate::api::identities::Color;
+
+/// How much colour the terminal we're writing to can show
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum ColorSupport {
+    None,
+    Ansi256,
+    TrueColor,
+}
+
+impl ColorSupport {
+    /// Based on whether stdout is a terminal, the `NO_COLOR` convention and the terminal's
+    /// advertised capabilities
+    pub(super) fn detect() -> Self {
+        if std::env::var_os("NO_COLOR").is_some() || !atty::is(atty::Stream::Stdout) {
+            return Self::None;
+        }
+        let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+        let term = std::env::var("TERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            Self::TrueColor
+        } else if term.contains("256color") {
+            Self::Ansi256
+        } else if term.is_empty() || term == "dumb" {
+            Self::None
+        } else {
+            // Most terminals that don't advertise it still understand 256 colours
+            Self::Ansi256
+        }
+    }
+
+    /// Shows `text` on a `background` colour, with a space either side so it looks like a tile
+    pub(super) fn on_background(self, text: &str, background: &Color) -> String {
+        let Color { r, g, b } = *background;
+        match self {
+            Self::None => text.to_owned(),
+            Self::Ansi256 => format!("\x1b[48;5;{}m {} \x1b[0m", ansi256(r, g, b), text),
+            Self::TrueColor => format!("\x1b[48;2;{};{};{}m {} \x1b[0m", r, g, b, text),
+        }
+    }
+}
+
+/// The closest colour in the 6×6×6 cube or the greyscale ramp of the 256 colour palette
+fn ansi256(r: u8, g: u8, b: u8) -> u8 {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    fn nearest_level(value: u8) -> usize {
+        (0..LEVELS.len())
+            .min_by_key(|&i| (i16::from(LEVELS[i]) - i16::from(value)).abs())
+            .unwrap()
+    }
+    fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> i32 {
+        let d = |a: u8, b: u8| (i32::from(a) - i32::from(b)).pow(2);
+        d(r1, r2) + d(g1, g2) + d(b1, b2)
+    }
+
+    let (ri, gi, bi) = (nearest_level(r), nearest_level(g), nearest_level(b));
+    let cube = (LEVELS[ri], LEVELS[gi], LEVELS[bi]);
+    let cube_index = 16 + 36 * ri + 6 * gi + bi;
+
+    // The ramp runs from 8 to 238 in steps of 10
+    let average = (u16::from(r) + u16::from(g) + u16::from(b)) / 3;
+    let grey_step = (average.saturating_sub(3) / 10).min(23) as u8;
+    let grey = 8 + 10 * grey_step;
+    let grey_index = 232 + usize::from(grey_step);
+
+    if distance((grey, grey, grey), (r, g, b)) < distance(cube, (r, g, b)) {
+        grey_index as u8
+    } else {
+        cube_index as u8
+    }
+}
@@ -0,0 +1,56 @@
+use crate::api::{Auth, AuthMechanism};
+use anyhow::{Context, Error};
+use secrecy::Secret;
+use std::{collections::BTreeMap, path::PathBuf};
+
+/// Settings that don't change between invocations, read from `config.toml` in the config dir
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(super) struct Config {
+    pub(super) auth: AuthConfig,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(super) struct AuthConfig {
+    pub(super) mechanism: AuthMechanism,
+    /// Extra headers sent with every request, e.g. for a gateway in front of the proxy
+    pub(super) headers: BTreeMap<String, Secret<String>>,
+}
+
+impl Config {
+    /// Where the config and other user maintained files like contacts live
+    pub(super) fn dir() -> Option<PathBuf> {
+        Some(dirs_next::config_dir()?.join(env!("CARGO_PKG_NAME")))
+    }
+
+    pub(super) fn path() -> Option<PathBuf> {
+        Some(Self::dir()?.join("config.toml"))
+    }
+
+    #[fehler::throws]
+    #[tracing::instrument]
+    /// Missing config files are treated as empty
+    pub(super) fn load() -> Self {
+        let path = match Self::path() {
+            Some(path) if path.exists() => path,
+            _ => return Self::default(),
+        };
+        let config = std::fs::read_to_string(&path)
+            .with_context(|| format!("Could not read config {}", path.display()))?;
+        toml::from_str(&config).with_context(|| format!("Invalid config {}", path.display()))?
+    }
+}
+
+impl AuthConfig {
+    pub(super) fn to_auth(&self, mechanism: Option<AuthMechanism>) -> Auth {
+        Auth {
+            mechanism: mechanism.unwrap_or(self.mechanism),
+            headers: self
+                .headers
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+        }
+    }
+}
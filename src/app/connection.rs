@@ -0,0 +1,305 @@
+use crate::api::{Api, AuthMechanism, Builder, Cache, ErrorVariant, Recorder, Replayer, Timings};
+use crate::app::{auth_token, config::Config, timings, ResultExt};
+use anyhow::Error;
+use secrecy::Secret;
+use std::{
+    num::{NonZeroU32, NonZeroUsize},
+    path::PathBuf,
+    time::Duration,
+};
+
+const DEFAULT_BASE_URL: &str = "http://localhost:17246";
+const DEFAULT_JOBS: &str = "4";
+const DEFAULT_CACHE_TTL: &str = "30";
+const DEFAULT_PASSPHRASE_ATTEMPTS: &str = "3";
+const DEFAULT_TIMINGS_FORMAT: &str = "table";
+
+/// Whether connecting may prompt for the passphrase if there is no valid auth token
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Login {
+    Prompt,
+    TokenOnly,
+}
+
+// How to connect to the proxy, only used once a command needs it (not a doc comment as that
+// would override the app description when flattened)
+#[derive(Debug, clap::Clap)]
+pub(super) struct Connection {
+    #[clap(long, default_value = DEFAULT_BASE_URL)]
+    base_url: url::Url,
+    /// Connect to a proxy listening on this unix domain socket instead of the base url's host
+    #[cfg(unix)]
+    #[clap(long)]
+    socket: Option<PathBuf>,
+    /// Maximum number of concurrent requests for commands that fetch data for many things
+    #[clap(long, short, default_value = DEFAULT_JOBS)]
+    jobs: NonZeroUsize,
+    /// Don't contact the proxy, only use cached responses
+    #[clap(long, conflicts_with = "no-cache")]
+    offline: bool,
+    /// Don't read or write the response cache
+    #[clap(long)]
+    no_cache: bool,
+    /// How many seconds cached responses are used for before refetching them
+    #[clap(long, default_value = DEFAULT_CACHE_TTL)]
+    cache_ttl: u64,
+    /// Write every request and response into this directory (with secrets redacted)
+    #[clap(long, conflicts_with_all = &["replay", "offline"])]
+    record: Option<PathBuf>,
+    /// Serve every request from a directory written by --record instead of contacting the proxy
+    #[clap(long, conflicts_with = "offline")]
+    replay: Option<PathBuf>,
+    /// How many times to prompt for the passphrase before giving up
+    #[clap(long, default_value = DEFAULT_PASSPHRASE_ATTEMPTS)]
+    passphrase_attempts: NonZeroU32,
+    /// How many seconds the auth token stays in the keyring after it was last used
+    #[clap(long)]
+    token_expiry: Option<u64>,
+    /// Print how long each request to the proxy took when the command finishes
+    #[clap(long)]
+    timings: bool,
+    /// How to print --timings: `table` or `json`
+    #[clap(long, default_value = DEFAULT_TIMINGS_FORMAT)]
+    timings_format: timings::Format,
+    /// How to send the auth token: `cookie` or `bearer` (overrides `auth.mechanism` in the config)
+    #[clap(long)]
+    auth: Option<AuthMechanism>,
+    /// PEM file of extra certificate authorities to trust for an https base url
+    #[cfg(feature = "tls")]
+    #[clap(long)]
+    ca_cert: Option<PathBuf>,
+    /// PEM file containing a client certificate chain and private key for an https base url
+    #[cfg(feature = "tls")]
+    #[clap(long)]
+    client_cert: Option<PathBuf>,
+    /// Don't verify the certificate of an https base url
+    #[cfg(feature = "tls")]
+    #[clap(long)]
+    insecure: bool,
+}
+
+impl Connection {
+    /// How to print the request timings, if they should be printed
+    pub(super) fn timings(&self) -> Option<timings::Format> {
+        if self.timings {
+            Some(self.timings_format)
+        } else {
+            None
+        }
+    }
+
+    /// Where the proxy is expected to be listening, for error messages
+    fn address(&self) -> String {
+        #[cfg(unix)]
+        if let Some(path) = &self.socket {
+            return path.display().to_string();
+        }
+        self.base_url.to_string()
+    }
+
+    pub(super) fn token_expiry(&self) -> Duration {
+        self.token_expiry
+            .map_or(auth_token::DEFAULT_AUTH_TOKEN_EXPIRY, Duration::from_secs)
+    }
+
+    #[fehler::throws]
+    #[tracing::instrument(fields(%self))]
+    /// Only returns `None` when `login` is `TokenOnly` and there is no valid auth token
+    pub(super) fn connect(&self, login: Login) -> Option<Api> {
+        let config = Config::load()?;
+        let mut builder = Api::builder(self.base_url.clone())
+            .jobs(self.jobs)
+            .auth(config.auth.to_auth(self.auth));
+        // Traces should contain every request, so don't let the cache serve any
+        let caching = !self.no_cache && self.record.is_none() && self.replay.is_none();
+        if caching {
+            if let Some(dir) = Cache::default_dir() {
+                let ttl = Duration::from_secs(self.cache_ttl);
+                builder = builder.cache(Cache::new(dir, ttl));
+            }
+        }
+        #[cfg(unix)]
+        if let Some(path) = &self.socket {
+            builder = builder.socket(path.clone());
+        }
+        #[cfg(feature = "tls")]
+        {
+            builder = builder.tls(crate::api::Tls {
+                ca_cert: self.ca_cert.clone(),
+                client_cert: self.client_cert.clone(),
+                insecure: self.insecure,
+            });
+        }
+        if self.timings {
+            builder = builder.timings(Timings::default());
+        }
+        if let Some(dir) = &self.record {
+            builder = builder.record(Recorder::new(dir.clone())?);
+        }
+
+        let api = if let Some(dir) = &self.replay {
+            builder.replay(Replayer::new(dir.clone())?).without_login()?
+        } else if self.offline {
+            builder.offline().without_login()?
+        } else {
+            match self.login(builder.clone(), login) {
+                Ok(Some(api)) => api,
+                Ok(None) => return None,
+                Err(err) if is_unreachable(&err) && caching => {
+                    tracing::debug!("{:?}", err);
+                    let reason = format!(
+                        "Could not connect to the proxy at {}: {}",
+                        self.address(),
+                        err.root_cause()
+                    );
+                    builder.unreachable(reason).without_login()?
+                }
+                Err(err) => fehler::throw!(err),
+            }
+        };
+
+        Some(api)
+    }
+
+    #[fehler::throws]
+    fn login(&self, builder: Builder, login: Login) -> Option<Api> {
+        let auth_token = match login {
+            Login::Prompt => auth_token::load(self.token_expiry()).ok_or_debug(),
+            // Just checking whether we're logged in shouldn't extend the expiry
+            Login::TokenOnly => auth_token::peek().ok_or_debug().map(|(token, _)| token),
+        };
+        if let Some(auth_token) = auth_token {
+            if let Some(api) = builder.clone().with_token(auth_token)? {
+                return Some(api);
+            }
+        }
+        if login == Login::TokenOnly {
+            return None;
+        }
+
+        let attempts = self.passphrase_attempts.get();
+        let mut attempt = 1;
+        loop {
+            match builder.clone().with_login(get_passphrase()?) {
+                Ok((api, auth_token)) => {
+                    auth_token::store(auth_token, self.token_expiry()).ok_or_debug();
+                    return Some(api);
+                }
+                Err(err) if is_wrong_passphrase(&err) && attempt < attempts => {
+                    eprintln!(
+                        "That's the wrong passphrase, please try again ({} of {} attempts)",
+                        attempt, attempts
+                    );
+                    attempt += 1;
+                }
+                Err(err) if is_wrong_passphrase(&err) => fehler::throw!(err.context(format!(
+                    "Wrong passphrase, giving up after {} attempt(s)",
+                    attempts
+                ))),
+                Err(err) if is_missing_keystore(&err) => fehler::throw!(err.context(
+                    "No keystore found, create an identity in Radicle Upstream first"
+                )),
+                Err(err) if is_unreachable(&err) => fehler::throw!(err.context(format!(
+                    "Could not connect to the proxy at {}",
+                    self.address()
+                ))),
+                Err(err) => fehler::throw!(err),
+            }
+        }
+    }
+}
+
+fn api_error(err: &Error) -> Option<&crate::api::Error> {
+    err.chain().find_map(|err| err.downcast_ref())
+}
+
+fn is_wrong_passphrase(err: &Error) -> bool {
+    match api_error(err) {
+        Some(crate::api::Error::Api { variant, code, .. }) => match variant {
+            ErrorVariant::IncorrectPassphrase => true,
+            ErrorVariant::Unknown(_) => *code == 403,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn is_missing_keystore(err: &Error) -> bool {
+    matches!(
+        api_error(err),
+        Some(crate::api::Error::Api {
+            variant: ErrorVariant::NotFound | ErrorVariant::Unauthorized,
+            ..
+        })
+    )
+}
+
+fn is_unreachable(err: &Error) -> bool {
+    matches!(api_error(err), Some(crate::api::Error::UreqTransport(_)))
+}
+
+#[fehler::throws]
+#[tracing::instrument]
+fn get_passphrase() -> Secret<String> {
+    Secret::new(rpassword::read_password_from_tty(Some(
+        "Please enter radicle passphrase: ",
+    ))?)
+}
+
+impl std::fmt::Display for Connection {
+    #[fehler::throws(std::fmt::Error)]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) {
+        if self.base_url != DEFAULT_BASE_URL.parse().unwrap() {
+            write!(f, " --base-url={}", self.base_url)?;
+        }
+        #[cfg(unix)]
+        if let Some(path) = &self.socket {
+            write!(f, " --socket={}", path.display())?;
+        }
+        if self.jobs != DEFAULT_JOBS.parse().unwrap() {
+            write!(f, " --jobs={}", self.jobs)?;
+        }
+        if self.offline {
+            write!(f, " --offline")?;
+        }
+        if self.no_cache {
+            write!(f, " --no-cache")?;
+        }
+        if self.cache_ttl != DEFAULT_CACHE_TTL.parse().unwrap() {
+            write!(f, " --cache-ttl={}", self.cache_ttl)?;
+        }
+        if let Some(dir) = &self.record {
+            write!(f, " --record={}", dir.display())?;
+        }
+        if let Some(dir) = &self.replay {
+            write!(f, " --replay={}", dir.display())?;
+        }
+        if self.passphrase_attempts != DEFAULT_PASSPHRASE_ATTEMPTS.parse().unwrap() {
+            write!(f, " --passphrase-attempts={}", self.passphrase_attempts)?;
+        }
+        if let Some(expiry) = self.token_expiry {
+            write!(f, " --token-expiry={}", expiry)?;
+        }
+        if self.timings {
+            write!(f, " --timings")?;
+        }
+        if self.timings_format != DEFAULT_TIMINGS_FORMAT.parse().unwrap() {
+            write!(f, " --timings-format={}", self.timings_format)?;
+        }
+        if let Some(auth) = self.auth {
+            write!(f, " --auth={}", auth)?;
+        }
+        #[cfg(feature = "tls")]
+        {
+            if let Some(path) = &self.ca_cert {
+                write!(f, " --ca-cert={}", path.display())?;
+            }
+            if let Some(path) = &self.client_cert {
+                write!(f, " --client-cert={}", path.display())?;
+            }
+            if self.insecure {
+                write!(f, " --insecure")?;
+            }
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use crate::api::identities::Identity;
+use crate::app::{config::Config, exit::Failure, WithContext};
+use anyhow::{Context, Error};
+use std::{collections::BTreeMap, io::Write, path::PathBuf};
+
+#[derive(Debug, clap::Clap)]
+/// Commands related to the local address book, which gives peers names only you see
+pub(super) struct App {
+    #[clap(subcommand)]
+    cmd: Cmd,
+}
+
+#[derive(Debug, clap::Clap)]
+pub(super) enum Cmd {
+    /// List all contacts
+    List,
+    /// Add a new contact
+    Add {
+        /// The name to use for the peer, can be used anywhere an identity is expected
+        name: String,
+        /// Full peer-id or URN of the identity
+        id: String,
+    },
+    /// Remove an existing contact
+    Remove { name: String },
+}
+
+/// The alphabet of the z-base-32 encoding used in peer ids and URNs
+const ZBASE32: &str = "ybndrfg8ejkmcpqxot1uwisza345h769";
+
+/// Every peer id is this long
+const PEER_ID_LEN: usize = 54;
+
+/// Whether `id` is a complete peer id or URN, contacts can't be prefixes
+fn is_full_id(id: &str) -> bool {
+    let is_zbase32 = |s: &str| !s.is_empty() && s.chars().all(|c| ZBASE32.contains(c));
+    match id.strip_prefix("rad:git:") {
+        Some(hash) => is_zbase32(hash),
+        None => id.len() == PEER_ID_LEN && is_zbase32(id),
+    }
+}
+
+/// Petnames for peer ids and URNs, stored in `contacts.toml` in the config dir
+#[derive(Debug, Default)]
+pub(super) struct Contacts {
+    /// From name to peer id or URN
+    entries: BTreeMap<String, String>,
+}
+
+impl Contacts {
+    fn path() -> Option<PathBuf> {
+        Some(Config::dir()?.join("contacts.toml"))
+    }
+
+    #[fehler::throws]
+    #[tracing::instrument]
+    /// A missing address book is treated as empty
+    pub(super) fn load() -> Self {
+        let path = match Self::path() {
+            Some(path) if path.exists() => path,
+            _ => return Self::default(),
+        };
+        let entries = std::fs::read_to_string(&path)
+            .with_context(|| format!("Could not read contacts {}", path.display()))?;
+        let entries = toml::from_str(&entries)
+            .with_context(|| format!("Invalid contacts {}", path.display()))?;
+        Self { entries }
+    }
+
+    #[fehler::throws]
+    #[tracing::instrument]
+    fn save(&self) {
+        let path = Self::path().context("Could not find the config dir to store contacts in")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, toml::to_string(&self.entries)?)
+            .with_context(|| format!("Could not write contacts {}", path.display()))?;
+    }
+
+    /// The peer id or URN saved under `name`
+    pub(super) fn get(&self, name: &str) -> Option<&str> {
+        self.entries.get(name).map(String::as_str)
+    }
+
+    /// The name saved for an identity's peer id or URN, if any
+    pub(super) fn name_of(&self, identity: &Identity) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(_, id)| **id == identity.peer_id || **id == identity.urn)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// `" [name]"` if there is a name for `identity`, to append to output that shows it
+    pub(super) fn decorate(&self, identity: &Identity) -> String {
+        self.name_of(identity)
+            .map_or_else(String::new, |name| format!(" [{}]", name))
+    }
+}
+
+impl WithContext<App> {
+    #[fehler::throws]
+    pub(super) fn run(self) {
+        self.map(|app| app.cmd).run()?
+    }
+}
+
+impl WithContext<Cmd> {
+    #[fehler::throws]
+    pub(super) fn run(self) {
+        let mut contacts = Contacts::load()?;
+        match self.as_ref() {
+            Cmd::List => {
+                for (name, id) in &contacts.entries {
+                    writeln!(self.output(), "{}: {}", name, id)?;
+                }
+            }
+            Cmd::Add { name, id } => {
+                anyhow::ensure!(is_full_id(id), "{} is not a full peer id or rad:git: URN", id);
+                if let Some(existing) = contacts.get(name) {
+                    anyhow::bail!(
+                        "Contact {} already exists for {}, remove it first",
+                        name,
+                        existing
+                    );
+                }
+                contacts.entries.insert(name.clone(), id.clone());
+                contacts.save()?;
+            }
+            Cmd::Remove { name } => {
+                if contacts.entries.remove(name).is_none() {
+                    fehler::throw!(Failure::NotFound(format!("No contact named {}", name)));
+                }
+                contacts.save()?;
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for App {
+    #[fehler::throws(std::fmt::Error)]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) {
+        write!(f, "contacts {}", self.cmd)?;
+    }
+}
+
+impl std::fmt::Display for Cmd {
+    #[fehler::throws(std::fmt::Error)]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) {
+        match self {
+            Self::List => write!(f, "list")?,
+            Self::Add { name, id } => write!(f, "add {:?} {:?}", name, id)?,
+            Self::Remove { name } => write!(f, "remove {:?}", name)?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_full_id;
+
+    #[test]
+    fn full_ids() {
+        assert!(is_full_id("hyn9diwfnytahjq8u3iw63h9jte1ydcatxax3saymwdxqu1zo645pe"));
+        assert!(is_full_id("rad:git:hnrkbtw9t1of4ykjy6er4qqwxtc54k9943eto"));
+    }
+
+    #[test]
+    fn partial_ids() {
+        assert!(!is_full_id("hyn9diwf"));
+        assert!(!is_full_id("rad:git:"));
+        assert!(!is_full_id("hnrkbtw9t1of4ykjy6er4qqwxtc54k9943eto"));
+        assert!(!is_full_id("alice"));
+        assert!(!is_full_id("hyn9diwfnytahjq8u3iw63h9jte1ydcatxax3saymwdxqu1zo645pE"));
+    }
+}
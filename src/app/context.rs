@@ -1,17 +1,32 @@
-use crate::api::Api;
-use std::{io::Write, sync::Mutex};
+use super::{color::ColorSupport, connection::Login};
+use crate::api::{
+    version::{Feature, ProxyVersion},
+    Api,
+};
+use anyhow::{Context as _, Error};
+use once_cell::unsync::OnceCell;
+use std::{io::Write, rc::Rc, sync::Mutex};
 
 pub(super) struct Context {
-    api: Api,
+    api: Rc<LazyApi>,
     output: Mutex<Box<dyn Write>>,
+    color: ColorSupport,
+}
+
+/// Connects to the proxy the first time a command uses it, so commands that don't need it never
+/// prompt for a passphrase
+pub(super) struct LazyApi {
+    api: OnceCell<Api>,
+    connect: Option<Box<dyn Fn(Login) -> Result<Option<Api>, Error>>>,
 }
 
 impl std::fmt::Debug for Context {
     #[fehler::throws(std::fmt::Error)]
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) {
         f.debug_struct("Context")
-            .field("api", &self.api)
+            .field("api", &self.api.connected())
             .field("output", &"..")
+            .field("color", &self.color)
             .finish()?;
     }
 }
@@ -36,11 +51,58 @@ impl<T> With for T {
 }
 
 impl Context {
-    pub(super) fn new(api: Api, output: impl Write + 'static) -> Self {
+    /// Without a `connect` function any attempt to use the api will fail
+    pub(super) fn new(
+        connect: Option<impl Fn(Login) -> Result<Option<Api>, Error> + 'static>,
+        output: impl Write + 'static,
+        color: ColorSupport,
+    ) -> Self {
         Context {
-            api,
+            api: Rc::new(LazyApi {
+                api: OnceCell::new(),
+                connect: connect.map(|connect| Box::new(connect) as Box<_>),
+            }),
             output: Mutex::new(Box::new(output)),
+            color,
+        }
+    }
+
+    /// A handle to the api that remains accessible after the context has been consumed
+    pub(super) fn lazy_api(&self) -> Rc<LazyApi> {
+        self.api.clone()
+    }
+}
+
+impl LazyApi {
+    #[fehler::throws]
+    fn connect(&self, login: Login) -> Option<Api> {
+        match &self.connect {
+            Some(connect) => connect(login)?,
+            None => anyhow::bail!("This command does not use the proxy"),
+        }
+    }
+
+    #[fehler::throws]
+    fn get(&self) -> &Api {
+        self.api.get_or_try_init(|| {
+            self.connect(Login::Prompt)?
+                .context("Connecting to the proxy failed without an error")
+        })?
+    }
+
+    #[fehler::throws]
+    fn get_without_prompt(&self) -> Option<&Api> {
+        if self.api.get().is_none() {
+            if let Some(api) = self.connect(Login::TokenOnly)? {
+                let _ = self.api.set(api);
+            }
         }
+        self.api.get()
+    }
+
+    /// The api, if a command has already connected to the proxy
+    pub(super) fn connected(&self) -> Option<&Api> {
+        self.api.get()
     }
 }
 
@@ -49,14 +111,41 @@ impl<T> WithContext<T> {
         &self.wrapped
     }
 
+    #[fehler::throws]
     pub(super) fn api(&self) -> &Api {
-        &self.context.api
+        self.context.api.get()?
+    }
+
+    /// The api, failing if the proxy is known not to support `feature` and warning if its version
+    /// is unknown
+    #[fehler::throws]
+    pub(super) fn require(&self, feature: Feature) -> &Api {
+        let api = self.api()?;
+        if let version @ ProxyVersion::Unknown(_) = api.proxy_version() {
+            eprintln!(
+                "warning: radicle-proxy version {} is not known to be compatible, {} may fail",
+                version, feature
+            );
+        }
+        api.require(feature)?;
+        api
+    }
+
+    /// The api, only if there is already a valid auth token (or we're offline), never prompts
+    #[fehler::throws]
+    pub(super) fn api_without_prompt(&self) -> Option<&Api> {
+        self.context.api.get_without_prompt()?
     }
 
     pub(super) fn output(&self) -> impl std::ops::DerefMut<Target = impl Write> + '_ {
         self.context.output.lock().unwrap()
     }
 
+    /// What colours can be used in `output`
+    pub(super) fn color(&self) -> ColorSupport {
+        self.context.color
+    }
+
     pub(super) fn map<U>(self, f: impl FnOnce(T) -> U) -> WithContext<U> {
         WithContext {
             wrapped: f(self.wrapped),
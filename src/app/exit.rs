@@ -0,0 +1,69 @@
+use crate::api::ErrorVariant;
+use anyhow::Error;
+
+/// The process exit codes, so scripts can react to failures without parsing output (keep in sync
+/// with the table in the README)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+crate enum ExitCode {
+    /// Any failure not covered by a more specific code
+    Failure = 1,
+    /// Invalid command line arguments
+    Usage = 2,
+    /// The requested identity, project, etc. does not exist
+    NotFound = 3,
+    /// An identifier matched more than one thing
+    Ambiguous = 4,
+    /// Logging in to the proxy failed, or it rejected our credentials
+    AuthFailure = 5,
+    /// Could not connect to the proxy at all
+    ProxyUnreachable = 6,
+    /// The proxy returned some other error response
+    ApiError = 7,
+}
+
+/// Command outcomes that are not errors from the proxy, but should still be reported as failures
+#[derive(Debug, thiserror::Error)]
+crate enum Failure {
+    #[error("{0}")]
+    NotFound(String),
+
+    #[error("{0}")]
+    Ambiguous(String),
+}
+
+impl From<&Error> for ExitCode {
+    fn from(err: &Error) -> Self {
+        for err in err.chain() {
+            if let Some(failure) = err.downcast_ref::<Failure>() {
+                return match failure {
+                    Failure::NotFound(_) => Self::NotFound,
+                    Failure::Ambiguous(_) => Self::Ambiguous,
+                };
+            }
+            if let Some(err) = err.downcast_ref::<crate::api::Error>() {
+                return match err {
+                    crate::api::Error::Api { variant, code, .. } => match variant {
+                        ErrorVariant::NotFound => Self::NotFound,
+                        ErrorVariant::KeystoreSealed
+                        | ErrorVariant::IncorrectPassphrase
+                        | ErrorVariant::Unauthorized => Self::AuthFailure,
+                        _ if *code == 403 => Self::AuthFailure,
+                        _ => Self::ApiError,
+                    },
+                    crate::api::Error::UreqTransport(_)
+                    | crate::api::Error::NotCached(_)
+                    | crate::api::Error::Offline
+                    | crate::api::Error::Unreachable(_) => Self::ProxyUnreachable,
+                    _ => Self::Failure,
+                };
+            }
+        }
+        Self::Failure
+    }
+}
+
+impl ExitCode {
+    crate fn exit(self) -> ! {
+        std::process::exit(self as i32)
+    }
+}
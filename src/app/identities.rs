@@ -1,7 +1,7 @@
-use crate::api::identities::Identity;
-use crate::app::WithContext;
+use crate::api::{identities::Identity, projects::Project, version::Feature};
+use crate::app::{contacts::Contacts, WithContext};
 use anyhow::Error;
-use std::io::Write;
+use std::{io::Write, path::PathBuf};
 
 #[derive(Debug, clap::Clap)]
 /// Commands related to identities
@@ -13,17 +13,74 @@ pub(super) struct App {
 #[derive(Debug, clap::Clap)]
 pub(super) enum Cmd {
     /// List all known identities
-    List,
+    List {
+        /// Show the full peer ids, instead of the shortest unique prefixes
+        #[clap(long)]
+        full: bool,
+    },
 
     /// Get details for an identity
     Get {
-        /// URN, handle or peer-id for the identity
+        /// Contact name, URN, handle or peer-id for the identity, or an unambiguous prefix of one
         id: String,
     },
 
     /// Get own identity details
     #[clap(name = "self")]
     This,
+
+    /// List the projects an identity maintains or contributes to
+    Projects {
+        /// Contact name, URN, handle or peer-id for the identity, or an unambiguous prefix of one
+        id: String,
+    },
+
+    /// Print the shareable identifier for an identity along with a QR code of it
+    Share {
+        /// Contact name, URN, handle or peer-id for the identity, or an unambiguous prefix of one
+        id: String,
+
+        /// Save the QR code to a `.png` or `.svg` file instead of printing it
+        #[clap(long, short)]
+        output: Option<PathBuf>,
+    },
+
+    /// Save the avatar for an identity as an SVG, or preview it in the terminal
+    Avatar {
+        /// Contact name, URN, handle or peer-id for the identity, or an unambiguous prefix of one
+        id: String,
+
+        /// Where to save the SVG, defaults to stdout unless it's a terminal. Only SVG is
+        /// supported, the proxy only provides an emoji and colour which are drawn as a tile
+        #[clap(long, short)]
+        output: Option<PathBuf>,
+    },
+}
+
+/// Peer ids are shown with at least this many characters, even if fewer would be unique
+const MIN_PEER_ID_PREFIX: usize = 8;
+
+/// The shortest prefix of each id that no other id starts with, in the same order as `ids`
+fn unique_prefixes<'a>(ids: &[&'a str]) -> Vec<&'a str> {
+    fn common(a: &str, b: &str) -> usize {
+        a.chars().zip(b.chars()).take_while(|(a, b)| a == b).count()
+    }
+
+    ids.iter()
+        .map(|id| {
+            let needed = ids
+                .iter()
+                .filter(|other| *other != id)
+                .map(|other| common(id, other) + 1)
+                .max()
+                .unwrap_or(0)
+                .max(MIN_PEER_ID_PREFIX);
+            match id.char_indices().nth(needed) {
+                Some((end, _)) => &id[..end],
+                None => id,
+            }
+        })
+        .collect()
 }
 
 impl WithContext<App> {
@@ -34,62 +91,124 @@ impl WithContext<App> {
 }
 
 impl WithContext<Cmd> {
+    /// The emoji on its background colour, as far as the terminal supports it
+    fn avatar(&self, identity: &Identity) -> String {
+        let avatar = &identity.avatar_fallback;
+        self.color().on_background(&avatar.emoji, &avatar.background)
+    }
+
     #[fehler::throws]
-    fn print_identities_list(&self, identities: &[Identity]) {
-        for identity in identities {
+    fn print_identity(&self, identity: &Identity) {
+        writeln!(self.output(), "{} {}", self.avatar(identity), identity.metadata.handle)?;
+        writeln!(self.output(), "{:#?}", identity)?;
+    }
+
+    #[fehler::throws]
+    /// With `full` unset peer ids are shortened to the shortest prefix unique among `identities`
+    fn print_identities_list(&self, identities: &[Identity], full: bool) {
+        let peer_ids = identities
+            .iter()
+            .map(|identity| identity.peer_id.as_str())
+            .collect::<Vec<_>>();
+        let peer_ids = if full {
+            peer_ids
+        } else {
+            unique_prefixes(&peer_ids)
+        };
+        let contacts = Contacts::load()?;
+        for (identity, peer_id) in identities.iter().zip(peer_ids) {
             writeln!(
                 self.output(),
-                "{} {}: {}",
-                identity.avatar_fallback.emoji,
+                "{} {}: {}{}",
+                self.avatar(identity),
                 identity.metadata.handle,
-                identity.peer_id
+                peer_id,
+                contacts.decorate(identity),
             )?;
         }
     }
 
     #[fehler::throws]
-    #[tracing::instrument]
-    fn find_matching_identities(&self, id: &str) -> Vec<Identity> {
-        self.api()
-            .identities()
-            .list()?
+    #[tracing::instrument(skip(identity), fields(urn = %identity.urn))]
+    /// The known projects `identity` is a maintainer of or a contributor to, with its role
+    fn projects_of(&self, identity: &Identity) -> Vec<(Project, String)> {
+        let api = self.api()?;
+        // Maintainers are in the project metadata, contributors are only known from the peers
+        let (maintained, others): (Vec<_>, Vec<_>) = self
+            .known_projects()?
             .into_iter()
-            .filter(|identity| {
-                identity.urn == id || identity.metadata.handle == id || identity.peer_id == id
-            })
-            .collect()
+            .map(|known| known.project)
+            .partition(|project| project.metadata.maintainers.contains(&identity.urn));
+        let mut found = maintained
+            .into_iter()
+            .map(|project| (project, "maintainer".to_owned()))
+            .collect::<Vec<_>>();
+        let version = api.proxy_version();
+        if version.supports(Feature::ProjectPeers) == Some(false) {
+            eprintln!(
+                "warning: radicle-proxy version {} does not support {}, only maintained projects \
+                 are listed",
+                version,
+                Feature::ProjectPeers
+            );
+        } else {
+            let results = api.concurrently(others, |api, project| {
+                let peers = api.projects().peers(&project.urn);
+                (project, peers)
+            });
+            for (project, peers) in results {
+                let role = peers?
+                    .into_iter()
+                    .find(|peer| {
+                        peer.peer_id == identity.peer_id || peer.status.user.urn == identity.urn
+                    })
+                    .map(|peer| peer.status.role)
+                    .filter(|role| role == "maintainer" || role == "contributor");
+                if let Some(role) = role {
+                    found.push((project, role));
+                }
+            }
+        }
+        found.sort_by(|(a, _), (b, _)| a.metadata.name.cmp(&b.metadata.name));
+        found
     }
 
     #[fehler::throws]
     pub(super) fn run(self) {
         match self.as_ref() {
-            Cmd::List => {
-                self.print_identities_list(&self.api().identities().list()?)?;
+            Cmd::List { full } => {
+                self.print_identities_list(&self.api()?.identities().list()?, *full)?;
             }
 
-            Cmd::Get { id } => match self.find_matching_identities(id)?.as_slice() {
-                [] => {
-                    writeln!(self.output(), "no identity matching '{}' found", id)?;
-                }
-                [identity] => {
-                    writeln!(self.output(), "{:#?}", identity)?;
-                }
-                identities => {
+            Cmd::Get { id } => {
+                self.print_identity(&self.resolve_identity(id)?)?;
+            }
+
+            Cmd::This => {
+                self.print_identity(&self.api()?.session().get()?.identity)?;
+            }
+
+            Cmd::Projects { id } => {
+                let identity = self.resolve_identity(id)?;
+                for (project, role) in self.projects_of(&identity)? {
                     writeln!(
                         self.output(),
-                        "\
-                            multiple identities matched '{}', \
-                            please use a urn/peer_id to guarantee uniqueness:
-                        ",
-                        id
+                        "{}: {} ({})",
+                        project.metadata.name,
+                        project.urn,
+                        role
                     )?;
-                    self.print_identities_list(identities)?;
                 }
-            },
+            }
 
-            Cmd::This => {
-                let identity = self.api().session().get()?.identity;
-                writeln!(self.output(), "{:#?}", identity)?;
+            Cmd::Share { id, output } => {
+                let identity = self.resolve_identity(id)?;
+                self.share(&identity.shareable_entity_identifier, output.as_deref())?;
+            }
+
+            Cmd::Avatar { id, output } => {
+                let identity = self.resolve_identity(id)?;
+                self.write_avatar(&identity.avatar_fallback, output.as_deref())?;
             }
         }
     }
@@ -106,9 +225,52 @@ impl std::fmt::Display for Cmd {
     #[fehler::throws(std::fmt::Error)]
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) {
         match self {
-            Self::List => write!(f, "list")?,
+            Self::List { full } => {
+                write!(f, "list")?;
+                if *full {
+                    write!(f, " --full")?;
+                }
+            }
             Self::Get { id } => write!(f, "get {:?}", id)?,
             Self::This => write!(f, "self")?,
+            Self::Projects { id } => write!(f, "projects {:?}", id)?,
+            Self::Share { id, output } => {
+                write!(f, "share {:?}", id)?;
+                if let Some(path) = output {
+                    write!(f, " --output={}", path.display())?;
+                }
+            }
+            Self::Avatar { id, output } => {
+                write!(f, "avatar {:?}", id)?;
+                if let Some(path) = output {
+                    write!(f, " --output={}", path.display())?;
+                }
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{unique_prefixes, MIN_PEER_ID_PREFIX};
+
+    #[test]
+    fn prefixes_have_minimum_length() {
+        let ids = ["hyn9diwfnytahjq8u3iw63h9jte1ydcatxax3saymwdxqu1zo645pe", "hybz9gfg"];
+        let prefixes = unique_prefixes(&ids);
+        assert_eq!(prefixes, ["hyn9diwf", "hybz9gfg"]);
+        assert_eq!(prefixes[0].len(), MIN_PEER_ID_PREFIX);
+    }
+
+    #[test]
+    fn prefixes_extend_past_common_part() {
+        let ids = ["hyn9diwfnytahjq8", "hyn9diwfnyxx", "hybz9gfgabc"];
+        assert_eq!(unique_prefixes(&ids), ["hyn9diwfnyt", "hyn9diwfnyx", "hybz9gfg"]);
+    }
+
+    #[test]
+    fn prefix_of_short_id_is_whole_id() {
+        assert_eq!(unique_prefixes(&["hyn"]), ["hyn"]);
+        assert_eq!(unique_prefixes(&["hyn9", "hyn9diwfnyt"]), ["hyn9", "hyn9diwf"]);
+    }
+}
@@ -1,16 +1,27 @@
+use self::connection::Connection;
 use self::context::{Context, With, WithContext};
-use crate::api::Api;
+use crate::api::ErrorVariant;
 use anyhow::Error;
-use secrecy::Secret;
 
+mod avatar;
+mod color;
+mod config;
+mod connection;
+mod contacts;
 mod context;
+mod exit;
 mod identities;
 mod projects;
+mod raw;
+mod resolve;
 mod seeds;
 mod session;
+mod share;
+mod timings;
+
+crate use self::exit::ExitCode;
 
 const APP_NAME: &str = env!("CARGO_BIN_NAME");
-const DEFAULT_BASE_URL: &str = "http://localhost:17246";
 
 #[derive(Debug, clap::Clap)]
 #[clap(name = APP_NAME, about, version)]
@@ -18,8 +29,8 @@ const DEFAULT_BASE_URL: &str = "http://localhost:17246";
 #[clap(global_setting(clap::AppSettings::DisableHelpSubcommand))]
 #[clap(global_setting(clap::AppSettings::GlobalVersion))]
 crate struct App {
-    #[clap(long, default_value = DEFAULT_BASE_URL)]
-    base_url: url::Url,
+    #[clap(flatten)]
+    connection: Connection,
     #[clap(subcommand)]
     cmd: Cmd,
 }
@@ -30,6 +41,9 @@ enum Cmd {
     Session(session::App),
     Seeds(seeds::App),
     Projects(projects::App),
+    Contacts(contacts::App),
+    #[clap(name = "api")]
+    Raw(raw::App),
 }
 
 trait ResultExt<T> {
@@ -48,31 +62,94 @@ impl<T, E: std::fmt::Debug> ResultExt<T> for Result<T, E> {
     }
 }
 
+/// A suggestion of what to try next for errors we recognise
+crate fn hint(err: &Error) -> Option<&'static str> {
+    let variant = err.chain().find_map(|err| match err.downcast_ref::<crate::api::Error>() {
+        Some(crate::api::Error::Api { variant, .. }) => Some(variant),
+        _ => None,
+    })?;
+    Some(match variant {
+        ErrorVariant::NotFound => "check the URN, `rad projects tracked` and `rad identities list` show what the proxy knows about",
+        ErrorVariant::AlreadyExists => "it already exists, there is nothing to do",
+        ErrorVariant::KeystoreSealed => "the keystore is sealed, rerun the command to be prompted for the passphrase",
+        ErrorVariant::IncorrectPassphrase => "the passphrase is the one chosen when creating the identity in Radicle Upstream",
+        ErrorVariant::InvalidInput => "the proxy rejected the input, check the arguments",
+        ErrorVariant::Unauthorized => "an identity must be created in Radicle Upstream before using the proxy",
+        ErrorVariant::Internal | ErrorVariant::Unknown(_) => return None,
+    })
+}
+
 #[cfg(target_os = "linux")]
 mod auth_token {
-    use anyhow::Error;
+    use anyhow::{Context, Error};
     use keyutils::{keytypes::user::User, Keyring, SpecialKeyring};
     use secrecy::{ExposeSecret, Secret};
+    use std::time::Duration;
 
     const AUTH_TOKEN_KEY: &str = "radicle-proxy-cli:auth_token";
-    const AUTH_TOKEN_EXPIRY: std::time::Duration = std::time::Duration::from_secs(15 * 60);
+    pub(super) const DEFAULT_AUTH_TOKEN_EXPIRY: Duration = Duration::from_secs(15 * 60);
+    pub(super) const STORE: &str = "linux user session keyring";
 
     #[fehler::throws]
     #[tracing::instrument]
-    pub(super) fn load() -> Secret<String> {
-        use keyutils::{keytypes::user::User, Keyring, SpecialKeyring};
+    /// Loads the token, and extends its expiry since it's being used
+    pub(super) fn load(expiry: Duration) -> Secret<String> {
         // SAFETY: Not actually unsafe: https://github.com/mathstuf/rust-keyutils/issues/56
         let session_keyring = unsafe { Keyring::new(SpecialKeyring::UserSession.serial()) };
         let mut key = session_keyring
             .search_for_key::<User, _, _>(AUTH_TOKEN_KEY, SpecialKeyring::Process)?;
         let auth_token = String::from_utf8(key.read()?)?;
-        key.set_timeout(AUTH_TOKEN_EXPIRY)?;
+        key.set_timeout(expiry)?;
         Secret::new(auth_token)
     }
 
     #[fehler::throws]
     #[tracing::instrument]
-    pub(super) fn store(auth_token: Secret<String>) {
+    /// Loads the token without extending its expiry, along with how long it has left (`None` if
+    /// it doesn't expire)
+    pub(super) fn peek() -> (Secret<String>, Option<Duration>) {
+        // SAFETY: Not actually unsafe: https://github.com/mathstuf/rust-keyutils/issues/56
+        let session_keyring = unsafe { Keyring::new(SpecialKeyring::UserSession.serial()) };
+        let key = session_keyring
+            .search_for_key::<User, _, _>(AUTH_TOKEN_KEY, SpecialKeyring::Process)?;
+        let auth_token = Secret::new(String::from_utf8(key.read()?)?);
+
+        // The remaining timeout is only exposed via /proc/keys, in a column like `14m` or `perm`
+        let serial = format!("{:08x}", key.serial().get());
+        let keys = std::fs::read_to_string("/proc/keys")?;
+        let timeout = keys
+            .lines()
+            .map(|line| line.split_whitespace().collect::<Vec<_>>())
+            .find(|columns| columns.first() == Some(&serial.as_str()))
+            .and_then(|columns| columns.get(3).copied())
+            .context("Auth token key missing from /proc/keys")?;
+        (auth_token, parse_timeout(timeout)?)
+    }
+
+    #[fehler::throws]
+    fn parse_timeout(timeout: &str) -> Option<Duration> {
+        if timeout == "perm" {
+            return None;
+        }
+        if timeout == "expd" {
+            return Some(Duration::from_secs(0));
+        }
+        let (count, unit) = timeout.split_at(timeout.len() - 1);
+        let count: u64 = count.parse()?;
+        let unit = match unit {
+            "s" => 1,
+            "m" => 60,
+            "h" => 60 * 60,
+            "d" => 24 * 60 * 60,
+            "w" => 7 * 24 * 60 * 60,
+            _ => anyhow::bail!("Unknown key timeout {:?}", timeout),
+        };
+        Some(Duration::from_secs(count * unit))
+    }
+
+    #[fehler::throws]
+    #[tracing::instrument]
+    pub(super) fn store(auth_token: Secret<String>, expiry: Duration) {
         // SAFETY: Not actually unsafe: https://github.com/mathstuf/rust-keyutils/issues/56
         let mut session_keyring = unsafe { Keyring::new(SpecialKeyring::UserSession.serial()) };
         let _ = session_keyring
@@ -80,54 +157,90 @@ mod auth_token {
         // Need to attach the key to the current process before we can set the timeout
         let mut key = session_keyring
             .search_for_key::<User, _, _>(AUTH_TOKEN_KEY, SpecialKeyring::Process)?;
-        key.set_timeout(AUTH_TOKEN_EXPIRY)?;
+        key.set_timeout(expiry)?;
     }
 }
 
 #[cfg(not(target_os = "linux"))]
 mod auth_token {
-    use crate::api::Api;
     use anyhow::Error;
     use secrecy::Secret;
+    use std::time::Duration;
+
+    pub(super) const DEFAULT_AUTH_TOKEN_EXPIRY: Duration = Duration::from_secs(15 * 60);
+    pub(super) const STORE: &str = "none (not supported on this platform)";
 
     #[fehler::throws]
     #[tracing::instrument]
-    fn load() -> Secret<String> {
-        None
+    pub(super) fn load(_expiry: Duration) -> Secret<String> {
+        anyhow::bail!("Storing the auth token is not supported on this platform")
     }
 
     #[fehler::throws]
     #[tracing::instrument]
-    fn store() {}
-}
+    pub(super) fn peek() -> (Secret<String>, Option<Duration>) {
+        anyhow::bail!("Storing the auth token is not supported on this platform")
+    }
 
-#[fehler::throws]
-#[tracing::instrument]
-fn get_passphrase() -> Secret<String> {
-    Secret::new(rpassword::read_password_from_tty(Some(
-        "Please enter radicle passphrase: ",
-    ))?)
+    #[fehler::throws]
+    #[tracing::instrument]
+    pub(super) fn store(_auth_token: Secret<String>, _expiry: Duration) {}
 }
 
 impl App {
     #[fehler::throws]
     #[tracing::instrument(fields(%self))]
     crate fn run(self) {
-        let api = if let Some(auth_token) = auth_token::load().ok_or_debug() {
-            if let Some(api) = Api::with_token(self.base_url.clone(), auth_token)? {
-                api
-            } else {
-                let (api, auth_token) = Api::with_login(self.base_url, get_passphrase()?)?;
-                auth_token::store(auth_token).ok_or_debug();
-                api
-            }
+        let start = std::time::Instant::now();
+        let Self { connection, cmd } = self;
+        let timings_format = connection.timings();
+        let connect = if cmd.offline_only() {
+            None
         } else {
-            let (api, auth_token) = Api::with_login(self.base_url, get_passphrase()?)?;
-            auth_token::store(auth_token).ok_or_debug();
-            api
+            Some(move |login| connection.connect(login))
         };
+        let color = color::ColorSupport::detect();
+        let context = Context::new(connect, std::io::stdout(), color);
+        let api = context.lazy_api();
+        let result = cmd.with(context).run();
+        if let Some(api) = api.connected() {
+            if let Some((count, oldest)) = api.stale().summary() {
+                if api.is_offline() && api.unreachable().is_none() {
+                    eprintln!(
+                        "warning: {} response(s) served from the cache are older than the cache \
+                         ttl, the oldest is {}s old",
+                        count,
+                        oldest.as_secs(),
+                    );
+                } else {
+                    eprintln!(
+                        "warning: the proxy is unreachable, {} response(s) were served from the \
+                         cache, the oldest is {}s old",
+                        count,
+                        oldest.as_secs(),
+                    );
+                }
+            }
+            if let (Some(format), Some(timings)) = (timings_format, api.timings()) {
+                timings::print(std::io::stderr(), &timings.all(), start.elapsed(), format)?;
+            }
+        }
+        result?;
+    }
+}
 
-        self.cmd.with(Context::new(api, std::io::stdout())).run()?;
+impl Cmd {
+    /// Commands that never need the proxy, these will fail instead of logging in if they try to
+    /// use it
+    fn offline_only(&self) -> bool {
+        match self {
+            Self::Contacts(_) => true,
+            Self::Identities(_)
+            | Self::Session(_)
+            | Self::Seeds(_)
+            | Self::Projects(_)
+            | Self::Raw(_) => false,
+        }
     }
 }
 
@@ -139,6 +252,8 @@ impl WithContext<Cmd> {
             Cmd::Session(app) => app.with(context).run(),
             Cmd::Seeds(app) => app.with(context).run(),
             Cmd::Projects(app) => app.with(context).run(),
+            Cmd::Contacts(app) => app.with(context).run(),
+            Cmd::Raw(app) => app.with(context).run(),
         })?;
     }
 }
@@ -147,9 +262,7 @@ impl std::fmt::Display for App {
     #[fehler::throws(std::fmt::Error)]
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) {
         write!(f, "{}", APP_NAME)?;
-        if self.base_url != DEFAULT_BASE_URL.parse().unwrap() {
-            write!(f, " --base-url={}", self.base_url)?;
-        }
+        write!(f, "{}", self.connection)?;
         write!(f, " {}", self.cmd)?;
     }
 }
@@ -162,6 +275,8 @@ impl std::fmt::Display for Cmd {
             Self::Session(app) => write!(f, "{}", app)?,
             Self::Seeds(app) => write!(f, "{}", app)?,
             Self::Projects(app) => write!(f, "{}", app)?,
+            Self::Contacts(app) => write!(f, "{}", app)?,
+            Self::Raw(app) => write!(f, "{}", app)?,
         }
     }
 }
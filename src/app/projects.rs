@@ -1,6 +1,7 @@
-use crate::app::WithContext;
+use crate::api::version::Feature;
+use crate::app::{contacts::Contacts, WithContext};
 use anyhow::Error;
-use std::io::Write;
+use std::{io::Write, path::PathBuf};
 
 #[derive(Debug, clap::Clap)]
 /// Commands related to projects
@@ -11,6 +12,29 @@ pub(super) struct App {
 
 #[derive(Debug, clap::Clap)]
 pub(super) enum Cmd {
+    /// List tracked and contributed projects, with how each relates to us
+    List {
+        /// Only list tracked projects
+        #[clap(long)]
+        tracked: bool,
+
+        /// Only list contributed projects
+        #[clap(long)]
+        contributed: bool,
+
+        /// Only list projects maintained by this identity (contact name, URN, handle or peer-id)
+        #[clap(long)]
+        maintainer: Option<String>,
+
+        /// Only list projects with a name matching this regex
+        #[clap(long)]
+        name: Option<regex::Regex>,
+
+        /// How to order the projects: `name`, `commits` or `contributors`
+        #[clap(long, default_value = DEFAULT_SORT)]
+        sort: Sort,
+    },
+
     /// Get the list of tracked projects
     Tracked,
 
@@ -22,15 +46,52 @@ pub(super) enum Cmd {
 
     /// Get a projects details
     Get {
-        /// URN for the project
-        urn: String,
+        /// Name or URN for the project, or an unambiguous prefix of one
+        id: String,
     },
 
-    /// Get the tracked peers for a project
+    /// Get the tracked peers for projects
     Peers {
-        /// URN for the project
-        urn: String,
+        /// Names or URNs for the projects, or unambiguous prefixes of them
+        #[clap(required_unless_present = "all")]
+        ids: Vec<String>,
+
+        /// Get the peers for all tracked projects
+        #[clap(long, conflicts_with = "ids")]
+        all: bool,
     },
+
+    /// Print the shareable identifier for a project along with a QR code of it
+    Share {
+        /// Name or URN for the project, or an unambiguous prefix of one
+        id: String,
+
+        /// Save the QR code to a `.png` or `.svg` file instead of printing it
+        #[clap(long, short)]
+        output: Option<PathBuf>,
+    },
+
+    /// Save the avatar for a project as an SVG, or preview it in the terminal
+    Avatar {
+        /// Name or URN for the project, or an unambiguous prefix of one
+        id: String,
+
+        /// Where to save the SVG, defaults to stdout unless it's a terminal. Only SVG is
+        /// supported, the proxy only provides an emoji and colour which are drawn as a tile
+        #[clap(long, short)]
+        output: Option<PathBuf>,
+    },
+}
+
+const DEFAULT_SORT: &str = "name";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Sort {
+    Name,
+    /// Most first
+    Commits,
+    /// Most first
+    Contributors,
 }
 
 impl WithContext<App> {
@@ -44,42 +105,142 @@ impl WithContext<Cmd> {
     #[fehler::throws]
     pub(super) fn run(self) {
         match self.as_ref() {
+            Cmd::List {
+                tracked,
+                contributed,
+                maintainer,
+                name,
+                sort,
+            } => {
+                let maintainer = match maintainer {
+                    Some(id) => Some(self.resolve_identity(id)?.urn),
+                    None => None,
+                };
+                let us = self.api()?.session().get()?.identity.urn;
+                let mut known = self
+                    .known_projects()?
+                    .into_iter()
+                    .filter(|known| {
+                        // Without either flag list both
+                        (!tracked && !contributed)
+                            || (*tracked && known.tracked)
+                            || (*contributed && known.contributed)
+                    })
+                    .filter(|known| {
+                        maintainer.as_ref().map_or(true, |maintainer| {
+                            known.project.metadata.maintainers.contains(maintainer)
+                        })
+                    })
+                    .filter(|known| {
+                        name.as_ref()
+                            .map_or(true, |name| name.is_match(&known.project.metadata.name))
+                    })
+                    .collect::<Vec<_>>();
+                match sort {
+                    Sort::Name => known.sort_by(|a, b| {
+                        a.project.metadata.name.cmp(&b.project.metadata.name)
+                    }),
+                    Sort::Commits => known.sort_by_key(|known| {
+                        std::cmp::Reverse(known.project.stats.commits)
+                    }),
+                    Sort::Contributors => known.sort_by_key(|known| {
+                        std::cmp::Reverse(known.project.stats.contributors)
+                    }),
+                }
+                for known in known {
+                    let project = &known.project;
+                    let relations = [
+                        (project.metadata.maintainers.contains(&us), "maintainer"),
+                        (known.tracked, "tracked"),
+                        (known.contributed, "contributed"),
+                    ]
+                    .iter()
+                    .filter(|(related, _)| *related)
+                    .map(|(_, relation)| *relation)
+                    .collect::<Vec<_>>();
+                    writeln!(
+                        self.output(),
+                        "{}: {} ({})",
+                        project.metadata.name,
+                        project.urn,
+                        relations.join(", ")
+                    )?;
+                }
+            }
+
             Cmd::Tracked => {
-                for project in self.api().projects().tracked()? {
+                for project in self.api()?.projects().tracked()? {
                     writeln!(self.output(), "{}: {}", project.metadata.name, project.urn)?;
                 }
             }
 
             Cmd::Contributed => {
-                for project in self.api().projects().contributed()? {
+                for project in self.api()?.projects().contributed()? {
                     writeln!(self.output(), "{}: {}", project.metadata.name, project.urn)?;
                 }
             }
 
             Cmd::Requested => {
-                for project in self.api().projects().requested()? {
+                self.require(Feature::ProjectRequests)?;
+                for project in self.api()?.projects().requested()? {
                     writeln!(self.output(), "{}: {:?}", project.urn, project.state)?;
                 }
             }
 
-            Cmd::Get { urn } => {
-                if let Some(project) = self.api().projects().get(&urn)? {
-                    writeln!(self.output(), "{:#?}", project)?;
+            Cmd::Get { id } => {
+                writeln!(self.output(), "{:#?}", self.resolve_project(id)?)?;
+            }
+
+            Cmd::Peers { ids, all } => {
+                self.require(Feature::ProjectPeers)?;
+                let urns: Vec<String> = if *all {
+                    self.api()?
+                        .projects()
+                        .tracked()?
+                        .into_iter()
+                        .map(|project| project.urn)
+                        .collect()
                 } else {
-                    writeln!(self.output(), "Project {} not found", urn)?;
+                    ids.iter()
+                        .map(|id| Ok(self.resolve_project(id)?.urn))
+                        .collect::<Result<_, Error>>()?
+                };
+                let multiple = urns.len() > 1;
+                let results = self.api()?.concurrently(urns, |api, urn| {
+                    let peers = api.projects().peers(&urn);
+                    (urn, peers)
+                });
+                let contacts = Contacts::load()?;
+                for (urn, peers) in results {
+                    let indent = if multiple {
+                        writeln!(self.output(), "{}:", urn)?;
+                        "  "
+                    } else {
+                        ""
+                    };
+                    for peer in peers? {
+                        writeln!(
+                            self.output(),
+                            "{}{} ({}){}: {}",
+                            indent,
+                            peer.status.user.metadata.handle,
+                            peer.peer_id,
+                            contacts.decorate(&peer.status.user),
+                            peer.status.role
+                        )?;
+                    }
                 }
             }
 
-            Cmd::Peers { urn } => {
-                for peer in self.api().projects().peers(&urn)? {
-                    writeln!(
-                        self.output(),
-                        "{} ({}): {}",
-                        peer.status.user.metadata.handle,
-                        peer.peer_id,
-                        peer.status.role
-                    )?;
-                }
+            Cmd::Share { id, output } => {
+                let project = self.resolve_project(id)?;
+                self.share(&project.shareable_entity_identifier, output.as_deref())?;
+            }
+
+            Cmd::Avatar { id, output } => {
+                let urn = self.resolve_project(id)?.urn;
+                let avatar = self.api()?.avatars().get(&urn)?;
+                self.write_avatar(&avatar, output.as_deref())?;
             }
         }
     }
@@ -96,11 +257,80 @@ impl std::fmt::Display for Cmd {
     #[fehler::throws(std::fmt::Error)]
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) {
         match self {
+            Self::List {
+                tracked,
+                contributed,
+                maintainer,
+                name,
+                sort,
+            } => {
+                write!(f, "list")?;
+                if *tracked {
+                    write!(f, " --tracked")?;
+                }
+                if *contributed {
+                    write!(f, " --contributed")?;
+                }
+                if let Some(maintainer) = maintainer {
+                    write!(f, " --maintainer={:?}", maintainer)?;
+                }
+                if let Some(name) = name {
+                    write!(f, " --name={:?}", name.as_str())?;
+                }
+                if *sort != DEFAULT_SORT.parse().unwrap() {
+                    write!(f, " --sort={}", sort)?;
+                }
+            }
             Self::Tracked => write!(f, "tracked")?,
             Self::Contributed => write!(f, "contributed")?,
             Self::Requested => write!(f, "requested")?,
-            Self::Get { urn } => write!(f, "get {:?}", urn)?,
-            Self::Peers { urn } => write!(f, "peers {:?}", urn)?,
+            Self::Get { id } => write!(f, "get {:?}", id)?,
+            Self::Peers { ids, all } => {
+                write!(f, "peers")?;
+                if *all {
+                    write!(f, " --all")?;
+                }
+                for id in ids {
+                    write!(f, " {:?}", id)?;
+                }
+            }
+            Self::Share { id, output } => {
+                write!(f, "share {:?}", id)?;
+                if let Some(path) = output {
+                    write!(f, " --output={}", path.display())?;
+                }
+            }
+            Self::Avatar { id, output } => {
+                write!(f, "avatar {:?}", id)?;
+                if let Some(path) = output {
+                    write!(f, " --output={}", path.display())?;
+                }
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Sort {
+    type Err = anyhow::Error;
+
+    #[fehler::throws(Self::Err)]
+    fn from_str(s: &str) -> Self {
+        match s {
+            "name" => Self::Name,
+            "commits" => Self::Commits,
+            "contributors" => Self::Contributors,
+            _ => anyhow::bail!("unknown sort {:?}, expected name, commits or contributors", s),
+        }
+    }
+}
+
+impl std::fmt::Display for Sort {
+    #[fehler::throws(std::fmt::Error)]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) {
+        match self {
+            Self::Name => write!(f, "name")?,
+            Self::Commits => write!(f, "commits")?,
+            Self::Contributors => write!(f, "contributors")?,
         }
     }
 }
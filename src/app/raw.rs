@@ -0,0 +1,56 @@
+use crate::api::raw::Method;
+use crate::app::WithContext;
+use anyhow::{Context, Error};
+use std::io::{Read, Write};
+
+#[derive(Debug, clap::Clap)]
+/// Send a request directly to an API endpoint and print the response, pretty-printed if it's JSON
+pub(super) struct App {
+    /// HTTP method: GET, POST, PUT, PATCH or DELETE
+    method: Method,
+
+    /// Path of the endpoint relative to the base url, may include a query, e.g. `v1/session`
+    path: String,
+
+    /// JSON request body, or `@<file>` to read it from a file (`@-` for stdin)
+    #[clap(long, short)]
+    data: Option<String>,
+}
+
+#[fehler::throws]
+fn read_data(data: &str) -> ureq::SerdeValue {
+    let data = match data.strip_prefix('@') {
+        Some("-") => {
+            let mut data = String::new();
+            std::io::stdin().read_to_string(&mut data)?;
+            data
+        }
+        Some(path) => std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read request body from {}", path))?,
+        None => data.to_owned(),
+    };
+    serde_json::from_str(&data).context("Request body is not valid JSON")?
+}
+
+impl WithContext<App> {
+    #[fehler::throws]
+    pub(super) fn run(self) {
+        let data = self.data.as_deref().map(read_data).transpose()?;
+        let body = self.api()?.raw().request(self.method, &self.path, data)?;
+        match serde_json::from_str::<ureq::SerdeValue>(&body) {
+            Ok(value) => writeln!(self.output(), "{}", serde_json::to_string_pretty(&value)?)?,
+            Err(_) => write!(self.output(), "{}", body)?,
+        }
+    }
+}
+
+impl std::fmt::Display for App {
+    #[fehler::throws(std::fmt::Error)]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) {
+        write!(f, "api {} {:?}", self.method, self.path)?;
+        if self.data.is_some() {
+            // Could contain secrets, e.g. when unsealing the keystore
+            write!(f, " --data=..")?;
+        }
+    }
+}
@@ -0,0 +1,303 @@
+use crate::api::{identities::Identity, projects::Project, ErrorVariant};
+use crate::app::{contacts::Contacts, exit::Failure, WithContext};
+use anyhow::Error;
+use std::fmt::Write;
+
+/// A project we know about, and how
+#[derive(Debug)]
+pub(super) struct Known {
+    pub(super) project: Project,
+    pub(super) tracked: bool,
+    pub(super) contributed: bool,
+}
+
+/// How well an identity or project matches the argument given, better matches sort first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Rank {
+    Exact,
+    NameIgnoringCase,
+    Prefix,
+    NamePrefixIgnoringCase,
+    /// The characters appear in the name in order, ignoring case
+    Fuzzy,
+}
+
+/// `name` is the handle or project name, `ids` are the URN and peer id, which only match exactly
+/// or by prefix
+fn rank(name: &str, ids: &[&str], id: &str) -> Option<Rank> {
+    // URNs are commonly abbreviated without the prefix
+    let is_prefix = |full: &&str| {
+        full.starts_with(id)
+            || full
+                .strip_prefix("rad:git:")
+                .map_or(false, |full| full.starts_with(id))
+    };
+    if id.is_empty() {
+        None
+    } else if name == id || ids.contains(&id) {
+        Some(Rank::Exact)
+    } else if name.eq_ignore_ascii_case(id) {
+        Some(Rank::NameIgnoringCase)
+    } else if ids.iter().any(is_prefix) {
+        Some(Rank::Prefix)
+    } else if name.to_lowercase().starts_with(&id.to_lowercase()) {
+        Some(Rank::NamePrefixIgnoringCase)
+    } else if is_subsequence(&id.to_lowercase(), &name.to_lowercase()) {
+        Some(Rank::Fuzzy)
+    } else {
+        None
+    }
+}
+
+/// Sorts the candidates by rank then name, returning the best if it is the only one with that rank
+fn best<T>(mut ranked: Vec<(Rank, String, T)>) -> Result<T, Vec<T>> {
+    ranked.sort_by(|(a, a_name, _), (b, b_name, _)| {
+        a.cmp(b).then_with(|| a_name.cmp(b_name))
+    });
+    let best = ranked.first().map(|&(rank, _, _)| rank);
+    let unique = ranked.iter().filter(|&&(rank, _, _)| Some(rank) == best).count() == 1;
+    if unique {
+        Ok(ranked.remove(0).2)
+    } else {
+        Err(ranked.into_iter().map(|(_, _, item)| item).collect())
+    }
+}
+
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut haystack = haystack.chars();
+    needle.chars().all(|c| haystack.any(|h| h == c))
+}
+
+/// What is being resolved, for messages
+struct Kind {
+    plural: &'static str,
+    singular: &'static str,
+    /// The ids that can't be ambiguous
+    full: &'static str,
+}
+
+const IDENTITY: Kind = Kind {
+    plural: "identities",
+    singular: "identity",
+    full: "urn/peer_id",
+};
+
+const PROJECT: Kind = Kind {
+    plural: "projects",
+    singular: "project",
+    full: "urn",
+};
+
+/// Gets a full URN directly, otherwise picks the best ranked candidate from `list`, `describe`
+/// shows the candidates if that's ambiguous
+#[fehler::throws]
+fn resolve<T>(
+    kind: &Kind,
+    id: &str,
+    get: impl FnOnce() -> Result<Option<T>, crate::api::Error>,
+    list: impl FnOnce() -> Result<Vec<(Rank, String, T)>, Error>,
+    describe: impl Fn(&T) -> String,
+) -> T {
+    if id.starts_with("rad:") {
+        match get() {
+            Ok(Some(found)) => return found,
+            // Probably a prefix, so fall back to matching against the list
+            Ok(None)
+            | Err(crate::api::Error::Api {
+                variant: ErrorVariant::InvalidInput,
+                ..
+            }) => {}
+            Err(err) => fehler::throw!(err),
+        }
+    }
+
+    let candidates = match best(list()?) {
+        Ok(found) => return found,
+        Err(candidates) if candidates.is_empty() => fehler::throw!(Failure::NotFound(format!(
+            "no {} matching '{}' found",
+            kind.singular, id
+        ))),
+        Err(candidates) => candidates,
+    };
+
+    let mut msg = format!("multiple {} matched '{}':", kind.plural, id);
+    // Worse matches are listed after the equally good ones
+    for candidate in &candidates {
+        write!(msg, "\n  {}", describe(candidate))?;
+    }
+    msg += "\nplease use a longer prefix or the full ";
+    msg += kind.full;
+    fehler::throw!(Failure::Ambiguous(msg));
+}
+
+impl<T> WithContext<T> {
+    #[fehler::throws]
+    #[tracing::instrument(skip(self))]
+    /// Resolves a contact name, URN, handle or peer id, or an unambiguous prefix of one, to a
+    /// single identity
+    ///
+    /// Contacts and full URNs are looked up exactly, everything else is matched against the list
+    /// of all identities.
+    pub(super) fn resolve_identity(&self, id: &str) -> Identity {
+        let contacts = Contacts::load()?;
+        let identities = self.api()?.identities();
+        if let Some(contact) = contacts.get(id) {
+            let identity = if contact.starts_with("rad:") {
+                identities.get(contact)?
+            } else {
+                identities
+                    .list()?
+                    .into_iter()
+                    .find(|identity| identity.peer_id == contact)
+            };
+            return identity.ok_or_else(|| {
+                Failure::NotFound(format!("contact '{}' is {}, which was not found", id, contact))
+            })?;
+        }
+
+        let list = || {
+            let ranked = identities
+                .list()?
+                .into_iter()
+                .filter_map(|identity| {
+                    let handle = &identity.metadata.handle;
+                    let ids = [identity.urn.as_str(), identity.peer_id.as_str()];
+                    let rank = rank(handle, &ids, id)?;
+                    Some((rank, handle.clone(), identity))
+                })
+                .collect::<Vec<_>>();
+            Ok::<_, Error>(ranked)
+        };
+        let describe = |identity: &Identity| {
+            format!(
+                "{} {}: {}{}",
+                identity.avatar_fallback.emoji,
+                identity.metadata.handle,
+                identity.peer_id,
+                contacts.decorate(identity),
+            )
+        };
+        resolve(&IDENTITY, id, || identities.get(id), list, describe)?
+    }
+
+    #[fehler::throws]
+    /// All tracked and contributed projects, once each
+    pub(super) fn known_projects(&self) -> Vec<Known> {
+        let api = self.api()?;
+        let mut known = api
+            .projects()
+            .tracked()?
+            .into_iter()
+            .map(|project| Known {
+                project,
+                tracked: true,
+                contributed: false,
+            })
+            .collect::<Vec<_>>();
+        for project in api.projects().contributed()? {
+            match known.iter_mut().find(|known| known.project.urn == project.urn) {
+                Some(known) => known.contributed = true,
+                None => known.push(Known {
+                    project,
+                    tracked: false,
+                    contributed: true,
+                }),
+            }
+        }
+        known
+    }
+
+    #[fehler::throws]
+    #[tracing::instrument(skip(self))]
+    /// Resolves a project name or URN, or an unambiguous prefix of one, to a single project
+    ///
+    /// Full URNs are looked up directly, everything else is matched against the tracked and
+    /// contributed projects.
+    pub(super) fn resolve_project(&self, id: &str) -> Project {
+        let projects = self.api()?.projects();
+        let list = || {
+            let ranked = self
+                .known_projects()?
+                .into_iter()
+                .filter_map(|Known { project, .. }| {
+                    let name = &project.metadata.name;
+                    let rank = rank(name, &[project.urn.as_str()], id)?;
+                    Some((rank, name.clone(), project))
+                })
+                .collect::<Vec<_>>();
+            Ok::<_, Error>(ranked)
+        };
+        let describe = |project: &Project| format!("{}: {}", project.metadata.name, project.urn);
+        resolve(&PROJECT, id, || projects.get(id), list, describe)?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{best, is_subsequence, rank, Rank};
+
+    /// Resolves `id` against `(name, ids)` candidates, returning the matching names
+    fn resolve<'a>(candidates: &[(&'a str, &[&str])], id: &str) -> Result<&'a str, Vec<&'a str>> {
+        best(
+            candidates
+                .iter()
+                .filter_map(|&(name, ids)| Some((rank(name, ids, id)?, name.to_owned(), name)))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn ranks() {
+        let urn = "rad:git:hnrkbtw9t1of4ykjy6er4qqwxtc54k9943eto";
+        assert_eq!(rank("alice", &[urn], "alice"), Some(Rank::Exact));
+        assert_eq!(rank("alice", &[urn], urn), Some(Rank::Exact));
+        assert_eq!(rank("Alice", &[urn], "alice"), Some(Rank::NameIgnoringCase));
+        assert_eq!(rank("alice", &[urn], "rad:git:hnrk"), Some(Rank::Prefix));
+        assert_eq!(rank("alice", &[urn], "Ali"), Some(Rank::NamePrefixIgnoringCase));
+        assert_eq!(rank("alice", &[urn], "ace"), Some(Rank::Fuzzy));
+        assert_eq!(rank("alice", &[urn], "bob"), None);
+        assert_eq!(rank("alice", &[urn], ""), None);
+    }
+
+    #[test]
+    fn urn_prefix_without_rad_git() {
+        let urn = "rad:git:hnrkbtw9t1of4ykjy6er4qqwxtc54k9943eto";
+        assert_eq!(rank("alice", &[urn], "hnrkbtw"), Some(Rank::Prefix));
+        assert_eq!(rank("alice", &[urn], "git:hnrk"), None);
+    }
+
+    #[test]
+    fn exact_beats_ignoring_case() {
+        let candidates: &[(&str, &[&str])] = &[("Alice", &[]), ("alice", &[])];
+        assert_eq!(resolve(candidates, "alice"), Ok("alice"));
+        assert_eq!(resolve(candidates, "Alice"), Ok("Alice"));
+    }
+
+    #[test]
+    fn prefix_beats_fuzzy() {
+        let candidates: &[(&str, &[&str])] =
+            &[("bob", &["rad:git:hbrk"]), ("hobbit", &["rad:git:hnrk"])];
+        assert_eq!(resolve(candidates, "hb"), Ok("bob"));
+    }
+
+    #[test]
+    fn ties_are_ambiguous() {
+        let candidates: &[(&str, &[&str])] = &[("alice2", &[]), ("bob", &[]), ("alice1", &[])];
+        assert_eq!(resolve(candidates, "ali"), Err(vec!["alice1", "alice2"]));
+        assert_eq!(resolve(candidates, "carol"), Err(vec![]));
+    }
+
+    #[test]
+    fn ambiguous_lists_worse_matches_last() {
+        let candidates: &[(&str, &[&str])] = &[("malice", &[]), ("alice", &[]), ("alicia", &[])];
+        assert_eq!(resolve(candidates, "ali"), Err(vec!["alice", "alicia", "malice"]));
+    }
+
+    #[test]
+    fn subsequence() {
+        assert!(is_subsequence("ace", "alice"));
+        assert!(is_subsequence("", "alice"));
+        assert!(!is_subsequence("ea", "alice"));
+        assert!(!is_subsequence("alicee", "alice"));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::api::version::Feature;
 use crate::app::WithContext;
 use anyhow::Error;
 use std::io::Write;
@@ -29,21 +30,22 @@ impl WithContext<App> {
 impl WithContext<Cmd> {
     #[fehler::throws]
     pub(super) fn run(self) {
+        self.require(Feature::SessionSettings)?;
         match self.as_ref() {
             Cmd::List => {
-                for seed in self.api().session().get()?.settings.coco.seeds {
+                for seed in self.api()?.session().get()?.settings.coco.seeds {
                     writeln!(self.output(), "{}", seed)?;
                 }
             }
             Cmd::Add { seed } => {
-                let mut settings = self.api().session().get()?.settings;
+                let mut settings = self.api()?.session().get()?.settings;
                 settings.coco.seeds.insert(seed.clone());
-                self.api().session().update_settings(settings)?;
+                self.api()?.session().update_settings(settings)?;
             }
             Cmd::Remove { seed } => {
-                let mut settings = self.api().session().get()?.settings;
+                let mut settings = self.api()?.session().get()?.settings;
                 settings.coco.seeds.remove(seed);
-                self.api().session().update_settings(settings)?;
+                self.api()?.session().update_settings(settings)?;
             }
         }
     }
@@ -1,6 +1,6 @@
-use crate::app::WithContext;
+use crate::app::{auth_token, ResultExt, WithContext};
 use anyhow::Error;
-use std::io::Write;
+use std::{io::Write, time::Duration};
 
 #[derive(Debug, clap::Clap)]
 /// Commands related to the current session
@@ -13,6 +13,21 @@ pub(super) struct App {
 pub(super) enum Cmd {
     /// Get the current session details
     Get,
+
+    /// Get the version of the proxy, as far as it can be detected
+    Version,
+
+    /// Check whether we're logged in, without prompting for the passphrase
+    Status,
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {}s", m, s),
+        (h, m, _) => format!("{}h {}m", h, m),
+    }
 }
 
 impl WithContext<App> {
@@ -27,9 +42,66 @@ impl WithContext<Cmd> {
     pub(super) fn run(self) {
         match self.as_ref() {
             Cmd::Get => {
-                let session = self.api().session().get()?;
+                let session = self.api()?.session().get()?;
                 writeln!(self.output(), "{:#?}", session)?;
             }
+
+            Cmd::Version => {
+                writeln!(self.output(), "{}", self.api()?.proxy_version())?;
+            }
+
+            Cmd::Status => {
+                let token = auth_token::peek().ok_or_debug();
+                match &token {
+                    Some((_, Some(ttl))) => writeln!(
+                        self.output(),
+                        "token: in the {}, expires in {}",
+                        auth_token::STORE,
+                        format_duration(*ttl)
+                    )?,
+                    Some((_, None)) => writeln!(
+                        self.output(),
+                        "token: in the {}, does not expire",
+                        auth_token::STORE
+                    )?,
+                    None => writeln!(self.output(), "token: none")?,
+                }
+
+                let api = match self.api_without_prompt() {
+                    Ok(api) => api,
+                    Err(err) => {
+                        writeln!(self.output(), "proxy: {:#}", err)?;
+                        None
+                    }
+                };
+                match api {
+                    Some(api) if api.unreachable().is_some() => writeln!(
+                        self.output(),
+                        "proxy: unreachable, token not checked, using cached data"
+                    )?,
+                    Some(api) if api.is_offline() => {
+                        writeln!(self.output(), "proxy: offline, token not checked")?
+                    }
+                    Some(api) if api.is_replaying() => {
+                        writeln!(self.output(), "proxy: replaying a trace, token not checked")?
+                    }
+                    Some(_) => writeln!(self.output(), "proxy: accepts the token")?,
+                    None if token.is_some() => writeln!(self.output(), "proxy: rejects the token")?,
+                    None => writeln!(self.output(), "proxy: not logged in")?,
+                }
+
+                if let Some(api) = api {
+                    let identity = api.session().get()?.identity;
+                    let avatar = &identity.avatar_fallback;
+                    writeln!(
+                        self.output(),
+                        "identity: {} {} ({})",
+                        self.color().on_background(&avatar.emoji, &avatar.background),
+                        identity.metadata.handle,
+                        identity.urn
+                    )?;
+                }
+            }
         }
     }
 }
@@ -46,6 +118,8 @@ impl std::fmt::Display for Cmd {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) {
         match self {
             Self::Get => write!(f, "get")?,
+            Self::Version => write!(f, "version")?,
+            Self::Status => write!(f, "status")?,
         }
     }
 }
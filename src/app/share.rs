@@ -0,0 +1,44 @@
+use crate::app::WithContext;
+use anyhow::{Context, Error};
+use qrcode::{render::unicode::Dense1x2, QrCode};
+use std::{io::Write, path::Path};
+
+impl<T> WithContext<T> {
+    #[fehler::throws]
+    /// Prints a shareable identifier along with a QR code of it, or writes the QR code to `path`
+    /// as a PNG or SVG depending on its extension
+    pub(super) fn share(&self, identifier: &str, path: Option<&Path>) {
+        let code = QrCode::new(identifier).context("Identifier is too long for a QR code")?;
+        writeln!(self.output(), "{}", identifier)?;
+        match path {
+            None => {
+                // Inverted so the code is dark on light when the terminal is light on dark
+                let code = code
+                    .render::<Dense1x2>()
+                    .dark_color(Dense1x2::Light)
+                    .light_color(Dense1x2::Dark)
+                    .build();
+                writeln!(self.output(), "{}", code)?;
+            }
+            Some(path) if path.extension().map_or(false, |extension| extension == "svg") => {
+                let code = code
+                    .render::<qrcode::render::svg::Color<'_>>()
+                    .min_dimensions(256, 256)
+                    .build();
+                std::fs::write(path, code)
+                    .with_context(|| format!("Failed to write QR code to {}", path.display()))?;
+            }
+            Some(path) if path.extension().map_or(false, |extension| extension == "png") => {
+                code.render::<image::Luma<u8>>()
+                    .min_dimensions(256, 256)
+                    .build()
+                    .save(path)
+                    .with_context(|| format!("Failed to write QR code to {}", path.display()))?;
+            }
+            Some(path) => anyhow::bail!(
+                "Unknown QR code format for {}, use a .png or .svg file",
+                path.display()
+            ),
+        }
+    }
+}
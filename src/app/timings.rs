@@ -0,0 +1,112 @@
+use crate::api::Timing;
+use anyhow::Error;
+use std::{io::Write, time::Duration};
+
+/// How to print the `--timings` summary
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Format {
+    Table,
+    Json,
+}
+
+impl std::str::FromStr for Format {
+    type Err = anyhow::Error;
+
+    #[fehler::throws(Self::Err)]
+    fn from_str(s: &str) -> Self {
+        match s {
+            "table" => Self::Table,
+            "json" => Self::Json,
+            _ => anyhow::bail!("unknown timings format {:?}, expected table or json", s),
+        }
+    }
+}
+
+impl std::fmt::Display for Format {
+    #[fehler::throws(std::fmt::Error)]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) {
+        match self {
+            Self::Table => write!(f, "table")?,
+            Self::Json => write!(f, "json")?,
+        }
+    }
+}
+
+#[fehler::throws]
+/// `total` is the time for the whole command, to compare against the time spent in requests
+pub(super) fn print(mut output: impl Write, timings: &[Timing], total: Duration, format: Format) {
+    // Concurrent requests overlap, so this can be more than `total`
+    let requests: Duration = timings.iter().map(|timing| timing.duration).sum();
+    match format {
+        Format::Table => {
+            let rows = timings
+                .iter()
+                .map(|timing| {
+                    [
+                        timing.method.clone(),
+                        timing.endpoint.clone(),
+                        timing.status.map_or("-".to_owned(), |status| status.to_string()),
+                        timing.size.map_or("-".to_owned(), |size| format!("{}B", size)),
+                        format!("{}ms", timing.duration.as_millis()),
+                    ]
+                })
+                .collect::<Vec<_>>();
+            let header = [
+                "METHOD".to_owned(),
+                "ENDPOINT".to_owned(),
+                "STATUS".to_owned(),
+                "SIZE".to_owned(),
+                "TIME".to_owned(),
+            ];
+            let mut widths = [0; 5];
+            for row in std::iter::once(&header).chain(&rows) {
+                for (width, column) in widths.iter_mut().zip(row) {
+                    *width = (*width).max(column.chars().count());
+                }
+            }
+            for row in std::iter::once(&header).chain(&rows) {
+                writeln!(
+                    output,
+                    "{:<w0$}  {:<w1$}  {:>w2$}  {:>w3$}  {:>w4$}",
+                    row[0],
+                    row[1],
+                    row[2],
+                    row[3],
+                    row[4],
+                    w0 = widths[0],
+                    w1 = widths[1],
+                    w2 = widths[2],
+                    w3 = widths[3],
+                    w4 = widths[4],
+                )?;
+            }
+            writeln!(
+                output,
+                "{} request(s) took {}ms, the command took {}ms",
+                timings.len(),
+                requests.as_millis(),
+                total.as_millis(),
+            )?;
+        }
+        Format::Json => {
+            let requests_json = timings
+                .iter()
+                .map(|timing| {
+                    serde_json::json!({
+                        "method": timing.method,
+                        "endpoint": timing.endpoint,
+                        "status": timing.status,
+                        "size": timing.size,
+                        "ms": timing.duration.as_secs_f64() * 1000.0,
+                    })
+                })
+                .collect::<Vec<_>>();
+            let summary = serde_json::json!({
+                "requests": requests_json,
+                "requests_ms": requests.as_secs_f64() * 1000.0,
+                "total_ms": total.as_secs_f64() * 1000.0,
+            });
+            writeln!(output, "{}", serde_json::to_string(&summary)?)?;
+        }
+    }
+}
@@ -11,12 +11,29 @@ use tracing_subscriber::EnvFilter;
 mod api;
 mod app;
 
-#[fehler::throws]
 fn main() {
     tracing_subscriber::fmt()
         .with_env_filter(EnvFilter::from_env("RAD_LOG"))
         .with_writer(std::io::stderr)
         .pretty()
         .init();
-    app::App::parse().run()?;
+    let app = match app::App::try_parse() {
+        Ok(app) => app,
+        Err(err) if err.use_stderr() => {
+            eprintln!("{}", err);
+            app::ExitCode::Usage.exit();
+        }
+        Err(err) => err.exit(),
+    };
+    if let Err(err) = app.run() {
+        report(&err);
+        app::ExitCode::from(&err).exit();
+    }
+}
+
+fn report(err: &Error) {
+    eprintln!("Error: {:?}", err);
+    if let Some(hint) = app::hint(err) {
+        eprintln!("\nhint: {}", hint);
+    }
 }